@@ -0,0 +1,170 @@
+use std::{fmt, fs, path::PathBuf, sync::Arc};
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{
+    de::{IgnoredAny, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+use crate::{error::GameError, prelude::*};
+
+use super::CharacterAnimation;
+
+/// The atlas and the animations of a sprite sheet exported from Aseprite with its json data.
+/// Every frame tag becomes an animation with the name of the tag.
+#[derive(Debug, Clone)]
+pub struct AsepriteSheet {
+    pub layout: TextureAtlasLayout,
+    pub animations: HashMap<Arc<str>, CharacterAnimation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AsepriteFile {
+    #[serde(deserialize_with = "frames_in_order")]
+    frames: Vec<AsepriteFrame>,
+    meta: AsepriteMeta,
+}
+
+// Aseprite exports the frames as an array or as a map keyed by file name, the map is read in the
+// order it is written, which is the order of the frames.
+fn frames_in_order<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<AsepriteFrame>, D::Error> {
+    struct FramesVisitor;
+
+    impl<'de> Visitor<'de> for FramesVisitor {
+        type Value = Vec<AsepriteFrame>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an array or a map of frames")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(
+            self,
+            mut seq: A,
+        ) -> std::result::Result<Self::Value, A::Error> {
+            let mut frames = Vec::new();
+            while let Some(frame) = seq.next_element()? {
+                frames.push(frame);
+            }
+            Ok(frames)
+        }
+
+        fn visit_map<A: MapAccess<'de>>(
+            self,
+            mut map: A,
+        ) -> std::result::Result<Self::Value, A::Error> {
+            let mut frames = Vec::new();
+            while let Some((_, frame)) = map.next_entry::<IgnoredAny, _>()? {
+                frames.push(frame);
+            }
+            Ok(frames)
+        }
+    }
+
+    deserializer.deserialize_any(FramesVisitor)
+}
+
+#[derive(Debug, Deserialize)]
+struct AsepriteFrame {
+    frame: AsepriteRect,
+    duration: u32, // milliseconds
+}
+
+#[derive(Debug, Deserialize)]
+struct AsepriteRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct AsepriteSize {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct AsepriteMeta {
+    size: AsepriteSize,
+    #[serde(rename = "frameTags", default)]
+    frame_tags: Vec<AsepriteTag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AsepriteTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: AsepriteDirection,
+    // How many times the tag plays, it loops forever when missing.
+    #[serde(default)]
+    repeat: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AsepriteDirection {
+    #[default]
+    Forward,
+    Reverse,
+    Pingpong,
+    PingpongReverse,
+}
+
+impl AsepriteSheet {
+    pub fn from_file(path: impl Into<PathBuf>) -> Result<Self> {
+        let bytes = fs::read(path.into())?;
+        Self::from_slice(&bytes)
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        let file: AsepriteFile = serde_json::from_slice(bytes)?;
+        let frames = file.frames;
+
+        let mut layout =
+            TextureAtlasLayout::new_empty(UVec2::new(file.meta.size.w, file.meta.size.h));
+        for frame in &frames {
+            let AsepriteRect { x, y, w, h } = frame.frame;
+            layout.add_texture(URect::new(x, y, x + w, y + h));
+        }
+
+        let mut animations = HashMap::new();
+        for tag in file.meta.frame_tags {
+            if tag.from > tag.to || tag.to >= frames.len() {
+                return Err(GameError::new(format!(
+                    "tag `{}` uses frames {}..={} but the sheet has {} frames",
+                    tag.name,
+                    tag.from,
+                    tag.to,
+                    frames.len()
+                )));
+            }
+
+            // The graph can not count repeats, a tag that repeats plays once and moves on.
+            // A ping pong always loops since the graph has no ping pong that finishes.
+            let playback = match (tag.direction, tag.repeat) {
+                (AsepriteDirection::Forward, None) => Playback::Loop,
+                (AsepriteDirection::Forward, Some(_)) => Playback::Once,
+                (AsepriteDirection::Reverse, None) => Playback::ReverseLoop,
+                (AsepriteDirection::Reverse, Some(_)) => Playback::Reverse,
+                (AsepriteDirection::Pingpong, _) => Playback::PingPong,
+                (AsepriteDirection::PingpongReverse, _) => Playback::PingPongReverse,
+            };
+            let durations = frames[tag.from..=tag.to]
+                .iter()
+                .map(|frame| frame.duration as f32 / 1000.)
+                .collect::<Vec<_>>();
+
+            let mut animation =
+                CharacterAnimation::new(tag.from, durations.len(), false, durations.iter().sum());
+            animation.frame_durations = Some(durations);
+            animation.playback = playback;
+            animations.insert(tag.name.into(), animation);
+        }
+
+        Ok(AsepriteSheet { layout, animations })
+    }
+}
@@ -0,0 +1,22 @@
+use std::sync::Arc;
+
+use bevy::utils::HashMap;
+
+use crate::prelude::*;
+
+/// One logical animation, like walking, with a variant for every value of an enum variable, like the
+/// direction the character faces. States play it like any other animation.
+#[derive(Debug, Clone)]
+pub struct DirectionalClip {
+    pub variable: usize,
+    pub variants: HashMap<String, Arc<str>>, // value of the variable and the animation played for it
+}
+
+impl DirectionalClip {
+    pub fn variant(&self, value: &Variable) -> Option<&Arc<str>> {
+        match value {
+            Variable::Enum(value) => self.variants.get(value),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt::Write;
+
+use crate::prelude::*;
+
+impl AnimationGraphDefinition {
+    /// Draws the graph in the Graphviz DOT format, so changes to its structure can be reviewed.
+    /// Nodes are labelled with their name and type, edges with the case or condition that takes them.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph animation_graph {\n");
+        dot.push_str("    node [fontname=\"monospace\"];\n");
+        dot.push_str("    edge [fontname=\"monospace\", fontsize=10];\n");
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let name = &self.node_names[index];
+            let (kind, shape) = match node {
+                NodeType::Root(_) => ("Root".to_string(), "doublecircle"),
+                NodeType::State(animation, _, priority, entry) => {
+                    let mut kind = "State".to_string();
+                    if animation != name {
+                        let _ = write!(kind, " `{animation}`");
+                    }
+                    if *priority > 0 {
+                        let _ = write!(kind, ", priority {priority}");
+                    }
+                    if entry.sync {
+                        kind.push_str(", sync");
+                    }
+                    if let Some(transition) = &entry.transition {
+                        let _ = write!(kind, ", through `{transition}`");
+                    }
+                    (kind, "box")
+                }
+                NodeType::Switch { .. } => ("Switch".to_string(), "hexagon"),
+                NodeType::Condition { .. } => ("Condition".to_string(), "diamond"),
+                NodeType::Setter(..) => ("Setter".to_string(), "parallelogram"),
+                NodeType::SubGraph(_) => ("SubGraph".to_string(), "box3d"),
+            };
+            let _ = writeln!(
+                dot,
+                "    n{index} [label=\"{}\\n{}\", shape={shape}];",
+                escape(name),
+                escape(&kind)
+            );
+        }
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let edges = match node {
+                NodeType::Root(next) | NodeType::SubGraph(next) => vec![(*next, String::new())],
+                NodeType::State(_, next, ..) => vec![(*next, "finished".to_string())],
+                NodeType::Switch {
+                    variables,
+                    cases,
+                    result,
+                    default,
+                } => cases
+                    .iter()
+                    .zip(result)
+                    .map(|(case, next)| (*next, self.describe_values(variables, case)))
+                    .chain(default.map(|next| (next, "default".to_string())))
+                    .collect(),
+                NodeType::Condition {
+                    variables,
+                    values,
+                    success,
+                    failure,
+                } => vec![
+                    (*success, self.describe_values(variables, values)),
+                    (*failure, "otherwise".to_string()),
+                ],
+                NodeType::Setter(variables, values, next) => {
+                    vec![(
+                        *next,
+                        format!("set {}", self.describe_values(variables, values)),
+                    )]
+                }
+            };
+
+            for (next, label) in edges {
+                let _ = writeln!(
+                    dot,
+                    "    n{index} -> n{next} [label=\"{}\"];",
+                    escape(&label)
+                );
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+
+use crate::{error::GameError, prelude::*};
+
+use super::{aseprite::AsepriteSheet, GraphReader};
+
+/// Label of the atlas layout of the sheet a graph uses, sprites can load it as `graph.anim.json#layout`
+/// so they follow the sheet when it is exported again.
+pub const LAYOUT_LABEL: &str = "layout";
+
+/// A graph loaded from a `.anim.json` file. Entities with an [`AnimationGraphHandle`] get a [`CharacterAnimationGraph`]
+/// playing its definition, which is replaced every time the file changes.
+#[derive(Asset, TypePath, Debug)]
+pub struct CharacterAnimationGraphAsset(pub Arc<AnimationGraphDefinition>);
+
+#[derive(Component, Debug, Clone)]
+pub struct AnimationGraphHandle(pub Handle<CharacterAnimationGraphAsset>);
+
+#[derive(Default)]
+pub struct CharacterAnimationGraphLoader;
+
+impl AssetLoader for CharacterAnimationGraphLoader {
+    type Asset = CharacterAnimationGraphAsset;
+    type Settings = ();
+    type Error = GameError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut graph = GraphReader::from_slice(&bytes)?;
+
+        // Reading the sheet through the load context reloads the graph when the sheet changes.
+        if let Some(sheet) = &graph.sheet {
+            let path = load_context.path().parent().map(|x| x.join(sheet));
+            let bytes = load_context
+                .read_asset_bytes(path.unwrap_or_else(|| sheet.into()))
+                .await?;
+            let sheet = AsepriteSheet::from_slice(&bytes)?;
+            graph.import_sheet(&sheet);
+            load_context.add_labeled_asset(LAYOUT_LABEL.to_string(), sheet.layout);
+        }
+
+        Ok(CharacterAnimationGraphAsset(Arc::new(
+            graph.into_definition()?,
+        )))
+    }
+
+    // Only `.anim.json` files are graphs, so other json assets can have loaders of their own.
+    fn extensions(&self) -> &[&str] {
+        &["anim.json"]
+    }
+}
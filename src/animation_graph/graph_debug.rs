@@ -0,0 +1,157 @@
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use crate::prelude::*;
+
+const DEBUG_TRANSITIONS: usize = 10;
+const DEBUG_FONT_SIZE: f32 = 14.;
+
+/// Draws the state of the animation graph of the entity marked with [`AnimationGraphDebugTarget`].
+/// F3 shows and hides the overlay.
+pub struct AnimationGraphDebugPlugin;
+
+/// Selects the entity whose animation graph the debug overlay shows.
+#[derive(Component, Debug, Default)]
+pub struct AnimationGraphDebugTarget;
+
+#[derive(Debug, Resource)]
+pub struct AnimationGraphDebugSettings {
+    /// How many of the last transitions of the selected graph are shown
+    pub transitions: usize,
+    pub toggle_key: KeyCode,
+}
+
+#[derive(Component)]
+struct AnimationGraphDebugText;
+
+// A target that was just selected or whose graph just finished loading.
+type NewDebugTarget = (
+    With<AnimationGraphDebugTarget>,
+    Or<(
+        Added<AnimationGraphDebugTarget>,
+        Added<CharacterAnimationGraph>,
+    )>,
+);
+
+impl Plugin for AnimationGraphDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AnimationGraphDebugSettings {
+            transitions: DEBUG_TRANSITIONS,
+            toggle_key: KeyCode::F3,
+        })
+        .add_systems(Startup, setup_debug_overlay)
+        .add_systems(
+            Update,
+            (
+                log_debug_transitions,
+                toggle_debug_overlay,
+                update_debug_overlay,
+            ),
+        );
+    }
+}
+
+fn setup_debug_overlay(mut commands: Commands) {
+    commands.spawn((
+        AnimationGraphDebugText,
+        Text::default(),
+        TextFont {
+            font_size: DEBUG_FONT_SIZE,
+            ..Default::default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.),
+            left: Val::Px(8.),
+            padding: UiRect::all(Val::Px(6.)),
+            ..Default::default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.6)),
+        Visibility::Hidden,
+    ));
+}
+
+// Graphs only log their transitions while they are being debugged.
+fn log_debug_transitions(
+    settings: Res<AnimationGraphDebugSettings>,
+    mut targets: Query<&mut CharacterAnimationGraph, NewDebugTarget>,
+    mut removed: RemovedComponents<AnimationGraphDebugTarget>,
+    mut graphs: Query<&mut CharacterAnimationGraph, Without<AnimationGraphDebugTarget>>,
+) {
+    for mut graph in &mut targets {
+        graph.log_transitions(settings.transitions);
+    }
+    for entity in removed.read() {
+        if let Ok(mut graph) = graphs.get_mut(entity) {
+            graph.log_transitions(0);
+        }
+    }
+}
+
+fn toggle_debug_overlay(
+    keys: Res<ButtonInput<KeyCode>>,
+    settings: Res<AnimationGraphDebugSettings>,
+    overlay: Single<&mut Visibility, With<AnimationGraphDebugText>>,
+) {
+    if keys.just_pressed(settings.toggle_key) {
+        overlay.into_inner().toggle_visible_hidden();
+    }
+}
+
+fn update_debug_overlay(
+    overlay: Single<(&mut Text, &Visibility), With<AnimationGraphDebugText>>,
+    targets: Query<(Entity, &CharacterAnimationGraph), With<AnimationGraphDebugTarget>>,
+) {
+    let (mut text, visibility) = overlay.into_inner();
+    if visibility == Visibility::Hidden {
+        return;
+    }
+
+    text.0 = match targets.iter().next() {
+        Some((entity, graph)) => describe_graph(entity, graph),
+        None => "no animation graph selected".to_string(),
+    };
+}
+
+fn describe_graph(entity: Entity, graph: &CharacterAnimationGraph) -> String {
+    let mut text = format!("animation graph of {entity}\n");
+    let _ = writeln!(
+        text,
+        "node: {}",
+        graph.definition.node_names[graph.current_node]
+    );
+    let _ = writeln!(
+        text,
+        "previous: {}",
+        graph.definition.node_names[graph.previous_node]
+    );
+    if let Some(pending) = graph.pending_priority() {
+        // A variable changed but the graph has not restarted yet, because its priority is too low for the node.
+        let _ = writeln!(
+            text,
+            "waiting to restart (priority {pending} < {})",
+            graph.priority()
+        );
+    }
+    if graph.paused {
+        let _ = writeln!(text, "paused");
+    }
+
+    let _ = writeln!(text, "\nvariables:");
+    let mut variables = graph.variables().collect::<Vec<_>>();
+    variables.sort_by_key(|(name, _)| *name);
+    for (name, value) in variables {
+        let _ = writeln!(text, "  {name} = {value}");
+    }
+
+    let _ = writeln!(text, "\ntransitions:");
+    for transition in graph.transitions() {
+        let _ = writeln!(
+            text,
+            "  {} -> {} ({})",
+            transition.from, transition.to, transition.cause
+        );
+    }
+    text
+}
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::prelude::*;
+
+/// Marks a child entity whose graph is a layer of the graph on its parent, for example a held item
+/// drawn over the body. The layer plays its own animations on its own sprite, but reads the variables
+/// of the parent graph, so gameplay only has to set them once.
+#[derive(Component, Debug, Default)]
+pub struct AnimationLayer {
+    synced: HashMap<Arc<str>, u32>, // the revision of each parent variable the layer has already seen
+}
+
+impl AnimationLayer {
+    /// Copies the variables of `parent` that changed since the last sync into `layer`.
+    /// Only changes are copied, so a trigger reaches the layer once even if the parent never uses it,
+    /// and again every time it is set after the parent used it.
+    /// A trigger is only copied when it is switched on, the layer consumes its own copy, so the parent
+    /// using the trigger first does not take it away from a layer that is still busy.
+    /// The changes keep the priority they have on the parent.
+    pub fn sync(&mut self, parent: &CharacterAnimationGraph, layer: &mut CharacterAnimationGraph) {
+        let priority = parent.pending_priority().unwrap_or_default();
+        for (name, value, revision) in parent.variable_revisions() {
+            let changed = self.synced.get(name) != Some(&revision);
+            let consumed = matches!(value, Variable::Trigger(false));
+            if changed && !consumed && layer.has_variable(name) {
+                layer.set_variable_with_priority(name, value.clone(), priority);
+            }
+            self.synced.insert(name.clone(), revision);
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use bevy::prelude::*;
+
+use super::{
+    graph_asset::{CharacterAnimationGraphAsset, CharacterAnimationGraphLoader},
+    graph_systems::*,
+    AnimationFrameEvent,
+};
+
+pub struct AnimationGraphPlugin;
+
+impl Plugin for AnimationGraphPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<CharacterAnimationGraphAsset>()
+            .init_asset_loader::<CharacterAnimationGraphLoader>()
+            .add_event::<AnimationFrameEvent>()
+            .add_systems(
+                Update,
+                (
+                    insert_loaded_graphs,
+                    reload_modified_graphs,
+                    sync_animation_layers,
+                    animate_graphs,
+                    log_animation_events,
+                )
+                    .chain(),
+            );
+    }
+}
@@ -0,0 +1,700 @@
+use std::{collections::HashMap, fs, path::PathBuf, sync::Arc};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{error::GameError, prelude::*};
+
+use super::{
+    aseprite::AsepriteSheet, directional::DirectionalClip, node_type::StateEntry,
+    variable::Comparison, CharacterAnimation,
+};
+
+// Name a state can use as `next` to go back to the root and re-evaluate the graph.
+const LOOP_NODE: &str = "loop";
+// Name the nodes of a sub-graph use to leave it.
+const EXIT_NODE: &str = "exit";
+// Declares a variable as a trigger instead of an enum.
+const TRIGGER: &str = "trigger";
+
+#[derive(Debug, Deserialize)]
+pub struct GraphReader {
+    // Aseprite json, relative to the graph file, whose tags are added to the animations.
+    #[serde(default)]
+    pub sheet: Option<String>,
+    #[serde(skip)]
+    pub sheet_animations: bevy::utils::HashMap<Arc<str>, CharacterAnimation>,
+    #[serde(default)]
+    pub animations: Vec<HashMap<String, AnimationDescription>>,
+    // Animations that states can play, which pick their variant from the value of a variable.
+    #[serde(default)]
+    pub directional: HashMap<String, DirectionalDescription>,
+    #[serde(default)]
+    pub variables: HashMap<String, Value>,
+    // Numeric variable that scales how fast the animations play.
+    #[serde(default)]
+    pub speed: Option<String>,
+    // Graphs that can be embedded with a sub-graph node, they share the animations and variables.
+    #[serde(default)]
+    pub graphs: HashMap<String, GraphBody>,
+    #[serde(flatten)]
+    pub body: GraphBody,
+}
+
+/// The nodes of a graph. `root` is the node the graph starts from.
+#[derive(Debug, Deserialize)]
+pub struct GraphBody {
+    pub root: String,
+    #[serde(default)]
+    pub switches: Vec<SwitchDescription>,
+    #[serde(default)]
+    pub conditions: Vec<ConditionDescription>,
+    #[serde(default)]
+    pub setters: Vec<SetterDescription>,
+    #[serde(default)]
+    pub states: Vec<StateDescription>,
+    #[serde(default)]
+    pub subgraphs: Vec<SubGraphDescription>,
+}
+
+// Either `start` and `count`, or the `tag` of an animation of the sheet that the other fields change,
+// for example to mirror it or to play it over a different total duration.
+#[derive(Debug, Deserialize)]
+pub struct AnimationDescription {
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub start: Option<usize>,
+    #[serde(default)]
+    pub count: Option<usize>,
+    #[serde(default)]
+    pub flip_x: bool,
+    // Can be left out when the frame durations are given, with them it scales the frames to this total.
+    pub animation_duration: Option<f32>,
+    #[serde(default)]
+    pub frame_durations: Option<Vec<f32>>,
+    #[serde(default)]
+    pub events: Vec<EventDescription>,
+    #[serde(default)]
+    pub playback: Option<Playback>,
+}
+
+// `animations` maps each value of the enum `variable` to an animation. A value without one mirrors
+// the animation of the value it is paired with in `mirrors`, left and right are paired by default.
+#[derive(Debug, Deserialize)]
+pub struct DirectionalDescription {
+    pub variable: String,
+    pub animations: HashMap<String, String>,
+    #[serde(default = "default_mirrors")]
+    pub mirrors: HashMap<String, String>,
+}
+
+fn default_mirrors() -> HashMap<String, String> {
+    HashMap::from([
+        ("left".to_string(), "right".to_string()),
+        ("right".to_string(), "left".to_string()),
+    ])
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventDescription {
+    pub frame: usize,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SwitchDescription {
+    pub name: String,
+    pub variables: Vec<String>,
+    pub cases: Vec<Vec<Value>>,
+    pub results: Vec<String>,
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConditionDescription {
+    pub name: String,
+    pub variables: Vec<String>,
+    pub values: Vec<Value>,
+    pub success: String,
+    pub failure: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetterDescription {
+    pub name: String,
+    pub variables: Vec<String>,
+    pub values: Vec<Value>,
+    pub next: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StateDescription {
+    pub current: String,
+    pub next: String,
+    // A locking state has priority 1, it can only be interrupted by changes with a priority.
+    #[serde(default)]
+    pub locking: bool,
+    #[serde(default)]
+    pub priority: Option<u32>,
+    // Start at the same point as the animation that was interrupted, to keep walk cycles in step.
+    #[serde(default)]
+    pub sync: bool,
+    // Animation played once before the one of the state, when the state is entered from another one.
+    #[serde(default)]
+    pub transition: Option<String>,
+}
+
+// Embeds the graph `graph` under the name `name`. Nodes of the embedded graph that go to "exit" continue with `next`.
+#[derive(Debug, Deserialize)]
+pub struct SubGraphDescription {
+    pub name: String,
+    pub graph: String,
+    pub next: String,
+}
+
+impl GraphReader {
+    /// Reads a graph and the sheet it uses, which is looked up next to the graph file.
+    pub fn from_file(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let text = fs::read_to_string(&path)?;
+        let mut reader = Self::from_json(&text)?;
+        if let Some(sheet) = &reader.sheet {
+            let sheet_path = path.parent().unwrap_or(&path).join(sheet);
+            reader.import_sheet(&AsepriteSheet::from_file(sheet_path)?);
+        }
+        Ok(reader)
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+
+    /// Adds the animations of `sheet`, the animations of the graph replace the ones with the same name.
+    pub fn import_sheet(&mut self, sheet: &AsepriteSheet) {
+        self.sheet_animations
+            .extend(sheet.animations.iter().map(|(x, y)| (x.clone(), y.clone())));
+    }
+
+    pub fn into_graph(self) -> Result<CharacterAnimationGraph> {
+        Ok(CharacterAnimationGraph::new(Arc::new(
+            self.into_definition()?,
+        )))
+    }
+
+    pub fn into_definition(self) -> Result<AnimationGraphDefinition> {
+        let mut animations = self.sheet_animations;
+        for (name, animation) in self.animations.into_iter().flatten() {
+            let animation = build_animation(&name, animation, &animations)?;
+            animations.insert(name.into(), animation);
+        }
+
+        // Sort the variables so that the indices do not depend on the map order.
+        let mut variable_names = self.variables.keys().cloned().collect::<Vec<_>>();
+        variable_names.sort();
+        let (declarations, domains): (Vec<_>, Vec<_>) = variable_names
+            .iter()
+            .map(|name| parse_declaration(name, &self.variables[name]))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        let name_to_variable: HashMap<String, usize> = variable_names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (name.clone(), index))
+            .collect();
+
+        let mut directional = bevy::utils::HashMap::new();
+        for (name, description) in self.directional {
+            if animations.contains_key(name.as_str()) {
+                return Err(GameError::new(format!(
+                    "animation `{name}` is declared twice"
+                )));
+            }
+            let clip = build_directional(
+                &name,
+                description,
+                &name_to_variable,
+                &domains,
+                &mut animations,
+            )?;
+            directional.insert(name.into(), clip);
+        }
+
+        let builder = NodeBuilder {
+            graphs: &self.graphs,
+            declarations: &declarations,
+            variable_names: &variable_names,
+            name_to_variable: &name_to_variable,
+            node_names: vec![LOOP_NODE.to_string()],
+            name_to_node: HashMap::new(),
+            nodes: Vec::new(),
+        };
+        let (node_names, nodes) = builder.clone().build_graph(&self.body, Vec::new(), None)?;
+
+        let mut definition = AnimationGraphDefinition {
+            animations,
+            directional,
+            name_to_variable: name_to_variable
+                .iter()
+                .map(|(name, index)| (name.as_str().into(), *index))
+                .collect(),
+            defaults: declarations.clone(),
+            domains,
+            speed_variable: None,
+            nodes,
+            node_names: node_names.into_iter().map(Into::into).collect(),
+        };
+        if let Some(speed) = &self.speed {
+            definition.speed_variable = Some(definition.speed_variable_index(speed)?);
+        }
+        definition.validate()?;
+
+        // Every named graph is also built on its own, with `exit` going back to its root, so that
+        // a graph nothing embeds yet is checked too.
+        let mut graph_names = self.graphs.keys().collect::<Vec<_>>();
+        graph_names.sort();
+        for name in graph_names {
+            let in_graph = |error: GameError| GameError::new(format!("in graph `{name}`: {error}"));
+            let (node_names, nodes) = builder
+                .clone()
+                .build_graph(&self.graphs[name], vec![name.clone()], Some(0))
+                .map_err(in_graph)?;
+            let graph = AnimationGraphDefinition {
+                animations: definition.animations.clone(),
+                directional: definition.directional.clone(),
+                name_to_variable: definition.name_to_variable.clone(),
+                defaults: definition.defaults.clone(),
+                domains: definition.domains.clone(),
+                speed_variable: definition.speed_variable,
+                nodes,
+                node_names: node_names.into_iter().map(Into::into).collect(),
+            };
+            graph.validate().map_err(|x| in_graph(x.into()))?;
+        }
+
+        Ok(definition)
+    }
+}
+
+#[derive(Clone)]
+struct NodeBuilder<'a> {
+    graphs: &'a HashMap<String, GraphBody>,
+    declarations: &'a [Variable],
+    variable_names: &'a [String],
+    name_to_variable: &'a HashMap<String, usize>,
+    node_names: Vec<String>,
+    name_to_node: HashMap<String, usize>,
+    nodes: Vec<NodeType>,
+}
+
+impl<'a> NodeBuilder<'a> {
+    // Node 0 is always the root, the rest follow in the order they are declared.
+    // The nodes of an embedded graph are named after the sub-graph node, like `locomotion/decideOnWalk`.
+    // `stack` holds the graph being built, if it is a named one, and `exit` is where its nodes that exit go.
+    fn build_graph(
+        mut self,
+        body: &GraphBody,
+        mut stack: Vec<String>,
+        exit: Option<usize>,
+    ) -> Result<(Vec<String>, Vec<NodeType>)> {
+        self.collect_names(body, "", &mut stack)?;
+        for (index, name) in self.node_names.iter().enumerate() {
+            if self.name_to_node.insert(name.clone(), index).is_some() {
+                return Err(GameError::new(format!("node `{name}` is declared twice")));
+            }
+        }
+
+        let root = self.resolve_node(&body.root, "", exit, "root")?;
+        self.nodes.push(NodeType::Root(root));
+        self.build(body, "", exit)?;
+        Ok((self.node_names, self.nodes))
+    }
+
+    // Names the nodes in the same order `build` creates them. `stack` holds the graphs being embedded,
+    // so that a graph that embeds itself is reported instead of growing forever.
+    fn collect_names(
+        &mut self,
+        body: &GraphBody,
+        prefix: &str,
+        stack: &mut Vec<String>,
+    ) -> Result<()> {
+        let names = body
+            .switches
+            .iter()
+            .map(|x| &x.name)
+            .chain(body.conditions.iter().map(|x| &x.name))
+            .chain(body.setters.iter().map(|x| &x.name))
+            .chain(body.states.iter().map(|x| &x.current));
+        for name in names {
+            self.node_names.push(format!("{prefix}{name}"));
+        }
+
+        for subgraph in &body.subgraphs {
+            let graph = self.subgraph(subgraph, prefix)?;
+            if stack.contains(&subgraph.graph) {
+                return Err(GameError::new(format!(
+                    "graph `{}` embeds itself through `{prefix}{}`",
+                    subgraph.graph, subgraph.name
+                )));
+            }
+            self.node_names.push(format!("{prefix}{}", subgraph.name));
+
+            stack.push(subgraph.graph.clone());
+            self.collect_names(graph, &format!("{prefix}{}/", subgraph.name), stack)?;
+            stack.pop();
+        }
+        Ok(())
+    }
+
+    fn build(&mut self, body: &GraphBody, prefix: &str, exit: Option<usize>) -> Result<()> {
+        for switch in &body.switches {
+            let owner = format!("{prefix}{}", switch.name);
+            let variables = self.resolve_variables(&switch.variables, &owner)?;
+            let cases = switch
+                .cases
+                .iter()
+                .map(|case| self.parse_values(&variables, case, &owner))
+                .collect::<Result<Vec<_>>>()?;
+            let result = switch
+                .results
+                .iter()
+                .map(|x| self.resolve_node(x, prefix, exit, &owner))
+                .collect::<Result<Vec<_>>>()?;
+            let default = switch
+                .default
+                .as_ref()
+                .map(|x| self.resolve_node(x, prefix, exit, &owner))
+                .transpose()?;
+            self.nodes.push(NodeType::Switch {
+                variables,
+                cases,
+                result,
+                default,
+            });
+        }
+
+        for condition in &body.conditions {
+            let owner = format!("{prefix}{}", condition.name);
+            let variables = self.resolve_variables(&condition.variables, &owner)?;
+            let values = self.parse_values(&variables, &condition.values, &owner)?;
+            self.nodes.push(NodeType::Condition {
+                variables,
+                values,
+                success: self.resolve_node(&condition.success, prefix, exit, &owner)?,
+                failure: self.resolve_node(&condition.failure, prefix, exit, &owner)?,
+            });
+        }
+
+        for setter in &body.setters {
+            let owner = format!("{prefix}{}", setter.name);
+            let variables = self.resolve_variables(&setter.variables, &owner)?;
+            let values = self.parse_values(&variables, &setter.values, &owner)?;
+            let next = self.resolve_node(&setter.next, prefix, exit, &owner)?;
+            self.nodes.push(NodeType::Setter(variables, values, next));
+        }
+
+        for state in &body.states {
+            let owner = format!("{prefix}{}", state.current);
+            let next = self.resolve_node(&state.next, prefix, exit, &owner)?;
+            self.nodes.push(NodeType::State(
+                state.current.as_str().into(),
+                next,
+                state.priority.unwrap_or(state.locking as u32),
+                StateEntry {
+                    sync: state.sync,
+                    transition: state.transition.as_deref().map(Into::into),
+                },
+            ));
+        }
+
+        for subgraph in &body.subgraphs {
+            let owner = format!("{prefix}{}", subgraph.name);
+            let graph = self.subgraph(subgraph, prefix)?;
+            let inner_prefix = format!("{owner}/");
+            let next = self.resolve_node(&subgraph.next, prefix, exit, &owner)?;
+            let entry = self.resolve_node(&graph.root, &inner_prefix, Some(next), &owner)?;
+            self.nodes.push(NodeType::SubGraph(entry));
+            self.build(graph, &inner_prefix, Some(next))?;
+        }
+        Ok(())
+    }
+
+    fn subgraph(&self, subgraph: &SubGraphDescription, prefix: &str) -> Result<&'a GraphBody> {
+        self.graphs.get(&subgraph.graph).ok_or_else(|| {
+            GameError::new(format!(
+                "unknown graph `{}` embedded by `{prefix}{}`",
+                subgraph.graph, subgraph.name
+            ))
+        })
+    }
+
+    fn resolve_node(
+        &self,
+        name: &str,
+        prefix: &str,
+        exit: Option<usize>,
+        owner: &str,
+    ) -> Result<usize> {
+        match name {
+            LOOP_NODE => Ok(0),
+            EXIT_NODE => exit.ok_or_else(|| {
+                GameError::new(format!("`{owner}` exits but it is not in a sub-graph"))
+            }),
+            _ => self
+                .name_to_node
+                .get(&format!("{prefix}{name}"))
+                .copied()
+                .ok_or_else(|| {
+                    GameError::new(format!("unknown node `{name}` referenced by `{owner}`"))
+                }),
+        }
+    }
+
+    fn resolve_variables(&self, names: &[String], owner: &str) -> Result<Vec<usize>> {
+        names
+            .iter()
+            .map(|name| {
+                self.name_to_variable.get(name).copied().ok_or_else(|| {
+                    GameError::new(format!("unknown variable `{name}` referenced by `{owner}`"))
+                })
+            })
+            .collect()
+    }
+
+    fn parse_values(
+        &self,
+        variables: &[usize],
+        values: &[Value],
+        owner: &str,
+    ) -> Result<Vec<Variable>> {
+        parse_values(
+            variables,
+            self.declarations,
+            self.variable_names,
+            values,
+            owner,
+        )
+    }
+}
+
+fn build_animation(
+    name: &str,
+    description: AnimationDescription,
+    sheet: &bevy::utils::HashMap<Arc<str>, CharacterAnimation>,
+) -> Result<CharacterAnimation> {
+    let mut animation = match (&description.tag, description.start, description.count) {
+        (Some(tag), None, None) => sheet.get(tag.as_str()).cloned().ok_or_else(|| {
+            GameError::new(format!("animation `{name}` uses the unknown tag `{tag}`"))
+        })?,
+        (None, Some(start), Some(count)) => {
+            let animation_duration =
+                match (&description.frame_durations, description.animation_duration) {
+                    (_, Some(duration)) => duration,
+                    (Some(durations), None) => durations.iter().sum(),
+                    (None, None) => {
+                        return Err(GameError::new(format!(
+                            "animation `{name}` needs an animation_duration or frame_durations"
+                        )))
+                    }
+                };
+            CharacterAnimation::new(start, count, false, animation_duration)
+        }
+        _ => {
+            return Err(GameError::new(format!(
+                "animation `{name}` needs either a tag or a start and a count"
+            )))
+        }
+    };
+
+    animation.flip_x |= description.flip_x;
+    if let Some(durations) = description.frame_durations {
+        animation.animation_duration = durations.iter().sum();
+        animation.frame_durations = Some(durations);
+    }
+    // A total duration stretches the frame durations, so each frame keeps its share of the animation.
+    if let Some(duration) = description.animation_duration {
+        if let Some(durations) = &mut animation.frame_durations {
+            let total = durations.iter().sum::<f32>();
+            for frame in durations.iter_mut() {
+                *frame *= duration / total;
+            }
+        }
+        animation.animation_duration = duration;
+    }
+    if let Some(playback) = description.playback {
+        animation.playback = playback;
+    }
+    animation.events.extend(
+        description
+            .events
+            .into_iter()
+            .map(|event| (event.frame, event.name.into())),
+    );
+    Ok(animation)
+}
+
+// Mirrored variants are added to the animations as `name/value`.
+fn build_directional(
+    name: &str,
+    description: DirectionalDescription,
+    name_to_variable: &HashMap<String, usize>,
+    domains: &[VariableDomain],
+    animations: &mut bevy::utils::HashMap<Arc<str>, CharacterAnimation>,
+) -> Result<DirectionalClip> {
+    let variable = *name_to_variable.get(&description.variable).ok_or_else(|| {
+        GameError::new(format!(
+            "unknown variable `{}` referenced by `{name}`",
+            description.variable
+        ))
+    })?;
+    let VariableDomain::Enum(values) = &domains[variable] else {
+        return Err(GameError::new(format!(
+            "`{name}` can only pick its animation from an enum, `{}` is not one",
+            description.variable
+        )));
+    };
+
+    for (value, animation) in &description.animations {
+        if !values.contains(value) {
+            return Err(GameError::new(format!(
+                "`{value}` is not a value of `{}` in `{name}`",
+                description.variable
+            )));
+        }
+        if !animations.contains_key(animation.as_str()) {
+            return Err(GameError::new(format!(
+                "`{name}` plays `{animation}` which is not an animation"
+            )));
+        }
+    }
+
+    let mut variants = bevy::utils::HashMap::new();
+    for value in values {
+        if let Some(animation) = description.animations.get(value) {
+            variants.insert(value.clone(), animation.as_str().into());
+            continue;
+        }
+
+        let mirrored = description
+            .mirrors
+            .get(value)
+            .and_then(|x| description.animations.get(x))
+            .ok_or_else(|| {
+                GameError::new(format!(
+                    "`{name}` has no animation for {} = {value}",
+                    description.variable
+                ))
+            })?;
+        let mut animation = animations[mirrored.as_str()].clone();
+        animation.flip_x = !animation.flip_x;
+        let variant: Arc<str> = format!("{name}/{value}").into();
+        animations.insert(variant.clone(), animation);
+        variants.insert(value.clone(), variant);
+    }
+
+    Ok(DirectionalClip { variable, variants })
+}
+
+// A declaration is either a boolean, a number, "trigger" or a comma separated list of enum values, the first one being the initial value.
+// Numbers written without a decimal point are integers and triggers start switched off.
+fn parse_declaration(name: &str, value: &Value) -> Result<(Variable, VariableDomain)> {
+    match value {
+        Value::Bool(x) => Ok((Variable::Bool(*x), VariableDomain::Bool)),
+        Value::Number(x) => match x.as_i64() {
+            Some(x) => Ok((Variable::Int(x), VariableDomain::Int)),
+            None => Ok((
+                Variable::Float(x.as_f64().unwrap_or_default() as f32),
+                VariableDomain::Float,
+            )),
+        },
+        Value::String(x) if x == TRIGGER => Ok((Variable::Trigger(false), VariableDomain::Trigger)),
+        Value::String(x) => {
+            let values = x
+                .split(',')
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            match values.first() {
+                Some(first) => Ok((Variable::Enum(first.clone()), VariableDomain::Enum(values))),
+                None => Err(GameError::new(format!("variable `{name}` has no values"))),
+            }
+        }
+        _ => Err(GameError::new(format!(
+            "variable `{name}` must be a bool, a number, a trigger or a list of values, found {value}"
+        ))),
+    }
+}
+
+// Values are checked against the kind of the variable they are assigned to. "*" matches anything
+// and numbers can be compared with a string such as ">= 50.0".
+fn parse_values(
+    variables: &[usize],
+    declarations: &[Variable],
+    variable_names: &[String],
+    values: &[Value],
+    owner: &str,
+) -> Result<Vec<Variable>> {
+    if values.len() != variables.len() {
+        return Err(GameError::new(format!(
+            "`{owner}` has {} values for {} variables",
+            values.len(),
+            variables.len()
+        )));
+    }
+
+    variables
+        .iter()
+        .zip(values)
+        .map(|(index, value)| match (&declarations[*index], value) {
+            (_, Value::String(x)) if x == "*" => Ok(Variable::Any),
+            (Variable::Bool(_), Value::Bool(x)) => Ok(Variable::Bool(*x)),
+            (Variable::Trigger(_), Value::Bool(x)) => Ok(Variable::Trigger(*x)),
+            (Variable::Enum(_), Value::String(x)) => Ok(Variable::Enum(x.clone())),
+            (Variable::Int(_), Value::Number(x)) if x.is_i64() => {
+                Ok(Variable::Int(x.as_i64().unwrap_or_default()))
+            }
+            (Variable::Float(_), Value::Number(x)) => {
+                Ok(Variable::Float(x.as_f64().unwrap_or_default() as f32))
+            }
+            (declaration @ (Variable::Int(_) | Variable::Float(_)), Value::String(x)) => {
+                parse_comparison(declaration, x).ok_or_else(|| {
+                    GameError::new(format!(
+                        "`{owner}` compares `{}` with `{x}` which is not a valid comparison",
+                        variable_names[*index]
+                    ))
+                })
+            }
+            _ => Err(GameError::new(format!(
+                "`{owner}` assigns {value} to `{}`",
+                variable_names[*index]
+            ))),
+        })
+        .collect()
+}
+
+fn parse_comparison(declaration: &Variable, text: &str) -> Option<Variable> {
+    let (comparison, number) = Comparison::OPERATORS
+        .iter()
+        .find_map(|(operator, comparison)| {
+            text.strip_prefix(operator)
+                .map(|number| (Some(*comparison), number))
+        })
+        .or_else(|| text.strip_prefix("==").map(|number| (None, number)))?;
+    let number = number.trim();
+
+    let value = match declaration {
+        Variable::Int(_) => Variable::Int(number.parse().ok()?),
+        _ => Variable::Float(number.parse().ok()?),
+    };
+    Some(match comparison {
+        Some(comparison) => Variable::Compare(comparison, Box::new(value)),
+        None => value,
+    })
+}
@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+
+use crate::prelude::*;
+
+use super::{
+    graph_asset::{AnimationGraphHandle, CharacterAnimationGraphAsset},
+    AnimationFrameEvent, AnimationLayer,
+};
+
+pub fn sync_animation_layers(
+    parents: Query<&CharacterAnimationGraph, Without<AnimationLayer>>,
+    mut layers: Query<(&Parent, &mut AnimationLayer, &mut CharacterAnimationGraph)>,
+) {
+    for (parent, mut layer, mut graph) in &mut layers {
+        if let Ok(parent_graph) = parents.get(parent.get()) {
+            layer.sync(parent_graph, &mut graph);
+        }
+    }
+}
+
+/// Ticks every animation graph and shows the frame it picked on the sprite of the entity.
+pub fn animate_graphs(
+    time: Res<Time>,
+    mut animation_events: EventWriter<AnimationFrameEvent>,
+    mut query: Query<(Entity, &mut CharacterAnimationGraph, &mut Sprite)>,
+) {
+    let delta = time.delta_secs();
+
+    for (entity, mut graph, mut sprite) in &mut query {
+        match graph.get_next_index(delta) {
+            Ok(Some(frame)) => {
+                if let Some(atlas) = sprite.texture_atlas.as_mut() {
+                    atlas.index = frame.index;
+                }
+                sprite.flip_x = frame.flip_x;
+            }
+            Ok(None) => {}
+            Err(error) => error!("animation of {entity} failed: {error}"),
+        }
+
+        for (animation, event) in graph.drain_events() {
+            animation_events.send(AnimationFrameEvent {
+                entity,
+                animation,
+                event,
+            });
+        }
+    }
+}
+
+pub fn insert_loaded_graphs(
+    mut commands: Commands,
+    graphs: Res<Assets<CharacterAnimationGraphAsset>>,
+    query: Query<(Entity, &AnimationGraphHandle), Without<CharacterAnimationGraph>>,
+) {
+    for (entity, handle) in &query {
+        if let Some(graph) = graphs.get(&handle.0) {
+            commands
+                .entity(entity)
+                .insert(CharacterAnimationGraph::new(graph.0.clone()));
+        }
+    }
+}
+
+pub fn reload_modified_graphs(
+    mut events: EventReader<AssetEvent<CharacterAnimationGraphAsset>>,
+    graphs: Res<Assets<CharacterAnimationGraphAsset>>,
+    mut query: Query<(&AnimationGraphHandle, &mut CharacterAnimationGraph)>,
+) {
+    for event in events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        let Some(graph) = graphs.get(*id) else {
+            continue;
+        };
+
+        info!("reloading animation graph {:?}", id);
+        for (handle, mut current) in &mut query {
+            if handle.0.id() == *id {
+                current.reload(&graph.0);
+            }
+        }
+    }
+}
+
+pub fn log_animation_events(mut animation_events: EventReader<AnimationFrameEvent>) {
+    for event in animation_events.read() {
+        debug!(
+            "{} fired `{}` from `{}`",
+            event.entity, event.event, event.animation
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use crate::prelude::*;
+
+/// A frame shown by a graph while running an [`AnimationScript`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationSample {
+    pub time: f32, // seconds since the script started
+    pub node: Arc<str>,
+    pub frame: usize,
+    pub flip_x: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum ScriptStep {
+    Set(String, Variable, u32), // variable, value and priority of the change
+    Tick(f32),
+}
+
+/// Drives a graph without a window or an app, so its behaviour can be checked in tests.
+/// The same script on the same graph always shows the same frames, with the same flip as
+/// [`AnimationGraphPlugin`](super::AnimationGraphPlugin) gives the sprite.
+#[derive(Debug, Clone, Default)]
+pub struct AnimationScript {
+    steps: Vec<ScriptStep>,
+}
+
+impl AnimationScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(self, name: impl Into<String>, value: Variable) -> Self {
+        self.set_with_priority(name, value, 0)
+    }
+
+    pub fn set_with_priority(
+        mut self,
+        name: impl Into<String>,
+        value: Variable,
+        priority: u32,
+    ) -> Self {
+        self.steps
+            .push(ScriptStep::Set(name.into(), value, priority));
+        self
+    }
+
+    pub fn tick(mut self, delta: f32) -> Self {
+        self.steps.push(ScriptStep::Tick(delta));
+        self
+    }
+
+    pub fn ticks(mut self, count: usize, delta: f32) -> Self {
+        self.steps
+            .extend(std::iter::repeat_n(ScriptStep::Tick(delta), count));
+        self
+    }
+
+    /// Runs the script and returns every frame the graph showed, in order.
+    pub fn run(&self, graph: &mut CharacterAnimationGraph) -> Result<Vec<AnimationSample>> {
+        let mut time = 0.0;
+        let mut samples = Vec::new();
+
+        for step in &self.steps {
+            match step {
+                ScriptStep::Set(name, value, priority) => {
+                    graph.set_variable_with_priority(name, value.clone(), *priority)
+                }
+                ScriptStep::Tick(delta) => {
+                    time += delta;
+                    let Some(frame) = graph.get_next_index(*delta)? else {
+                        continue;
+                    };
+                    samples.push(AnimationSample {
+                        time,
+                        node: graph.definition.node_names[graph.state_node].clone(),
+                        frame: frame.index,
+                        flip_x: frame.flip_x,
+                    });
+                }
+            }
+        }
+        Ok(samples)
+    }
+}
@@ -1,18 +1,46 @@
-use std::{sync::Arc, time::Duration};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
 use crate::prelude::*;
 use bevy::{prelude::*, utils::hashbrown::HashMap};
+use directional::DirectionalClip;
+use transition::{Transition, TransitionCause, TransitionLog};
 
+pub mod aseprite;
+pub mod directional;
+mod dot;
+mod graph_asset;
+mod graph_debug;
+mod graph_layer;
+mod graph_plugin;
+mod graph_reader;
+mod graph_systems;
+pub mod harness;
 pub mod node_type;
+pub mod playback;
+pub mod snapshot;
+pub mod transition;
+pub mod validation;
 pub mod variable;
 
+pub use graph_asset::{AnimationGraphHandle, LAYOUT_LABEL};
+pub use graph_debug::{AnimationGraphDebugPlugin, AnimationGraphDebugTarget};
+pub use graph_layer::AnimationLayer;
+pub use graph_plugin::AnimationGraphPlugin;
+pub use graph_reader::GraphReader;
+
+/// The fastest an animation graph can play, as a multiple of the normal speed.
+pub const MAX_SPEED: f32 = 100.0;
+
 // starting index and count
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CharacterAnimation {
     pub start: usize,
     pub count: usize,
     pub flip_x: bool,
     pub animation_duration: f32, // Total time to finish the animation
+    pub events: Vec<(usize, Arc<str>)>, // frame, counted from the start of the animation, and the name of the event it fires
+    pub frame_durations: Option<Vec<f32>>, // How long each frame is shown, when the frames are not evenly spaced
+    pub playback: Playback,
 }
 
 impl CharacterAnimation {
@@ -22,286 +50,429 @@ impl CharacterAnimation {
             count,
             flip_x,
             animation_duration,
+            events: Vec::new(),
+            frame_durations: None,
+            playback: Playback::Once,
+        }
+    }
+
+    pub fn frame_duration(&self, frame: usize) -> f32 {
+        match &self.frame_durations {
+            Some(durations) => durations.get(frame).copied().unwrap_or_default(),
+            None => self.animation_duration / self.count as f32,
+        }
+    }
+
+    /// The frame shown at `phase` of the animation, from 0.0 to 1.0, and how long it has been shown.
+    pub fn frame_at(&self, phase: f32) -> (usize, f32) {
+        let frames = if self.playback.is_reversed() {
+            (0..self.count).rev().collect::<Vec<_>>()
+        } else {
+            (0..self.count).collect()
+        };
+        let total = frames.iter().map(|x| self.frame_duration(*x)).sum::<f32>();
+
+        let mut time = phase.clamp(0.0, 1.0) * total;
+        for frame in &frames {
+            let duration = self.frame_duration(*frame);
+            if time < duration {
+                return (*frame, time);
+            }
+            time -= duration;
         }
+        (frames.last().copied().unwrap_or_default(), 0.0)
     }
 }
 
-#[derive(Component, Default, Debug)]
-pub struct CharacterAnimationGraph {
+/// A frame of the sprite sheet picked by [`CharacterAnimationGraph::get_next_index`], with the flip of the
+/// animation it belongs to. The node of the graph may already have moved on when the frame is the last one of its animation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationFrame {
+    pub index: usize,
+    pub flip_x: bool,
+}
+
+/// Sent when an animation shows a frame that has an event attached to it.
+#[derive(Event, Debug, Clone)]
+pub struct AnimationFrameEvent {
+    pub entity: Entity,
+    pub animation: Arc<str>,
+    pub event: Arc<str>,
+}
+
+/// The part of a graph read from its file, shared by every entity that plays it.
+#[derive(Debug)]
+pub struct AnimationGraphDefinition {
     animations: HashMap<Arc<str>, CharacterAnimation>,
+    directional: HashMap<Arc<str>, DirectionalClip>,
     name_to_variable: HashMap<Arc<str>, usize>,
-    variables: Vec<Variable>,
+    defaults: Vec<Variable>, // values of the variables when the graph starts
+    domains: Vec<VariableDomain>,
+    speed_variable: Option<usize>, // variable the speed is bound to when the graph starts
 
     nodes: Vec<NodeType>,
+    node_names: Vec<Arc<str>>,
+}
+
+/// The graph of one entity, only the variables and where the graph is are kept per entity.
+#[derive(Component, Debug, Clone)]
+pub struct CharacterAnimationGraph {
+    definition: Arc<AnimationGraphDefinition>,
+    variables: Vec<Variable>,
+    revisions: Vec<u32>, // how often each variable was set to a new value, using a trigger does not count
+
     previous_node: usize,
     current_node: usize,
-    next_frame_index: usize,
+    state_node: usize,               // the state whose animation is on screen
+    next_frame_index: Option<usize>, // frame of the current animation shown next, `None` before it starts
+    playing_backwards: bool,
+    current_frame: Option<(Arc<str>, usize)>, // animation and frame, counted from its start, that is on screen
+    fired_events: Vec<(Arc<str>, Arc<str>)>,  // animation and event names waiting to be drained
 
     timer: Timer,
+    speed: f32,
+    speed_variable: Option<usize>, // numeric variable that scales the speed
+    paused: bool,
+
+    playing_transition: bool, // the transition clip of the current state is on screen
+    entry_phase: Option<f32>, // progress of the interrupted animation, for a state that starts in sync with it
+    entry_offset: Duration,   // how long the first frame of a synced state has already been shown
 
     reseted: bool,
+    pending_priority: u32, // highest priority of the variable changes waiting for a restart
+    unmatched_switch: Option<usize>, // switch that had no case for the variables, it is reported only once
+    transitions: TransitionLog,
 }
 
-impl CharacterAnimationGraph {
-    pub fn get_current_animation(&self) -> Option<&CharacterAnimation> {
-        let current_node = &self.nodes[self.current_node];
-        match current_node {
-            NodeType::State(x, _, _) => self.animations.get(x),
-            _ => None,
+impl AnimationGraphDefinition {
+    pub fn from_file(path: impl Into<PathBuf>) -> Result<AnimationGraphDefinition> {
+        GraphReader::from_file(path)?.into_definition()
+    }
+
+    // Every animation a state playing `name` can show.
+    fn clips_of(&self, name: &Arc<str>) -> Vec<&CharacterAnimation> {
+        match self.directional.get(name) {
+            Some(clip) => clip
+                .variants
+                .values()
+                .filter_map(|x| self.animations.get(x))
+                .collect(),
+            None => self.animations.get(name).into_iter().collect(),
         }
     }
 
-    pub fn new() -> CharacterAnimationGraph {
-        let animations = vec![
-            (
-                "standingFront".into(),
-                CharacterAnimation::new(0, 6, false, 1.0),
-            ),
-            (
-                "standingLeft".into(),
-                CharacterAnimation::new(6, 6, false, 1.0),
-            ),
-            (
-                "standingRight".into(),
-                CharacterAnimation::new(6, 6, true, 1.0),
-            ),
-            (
-                "standingBack".into(),
-                CharacterAnimation::new(12, 6, false, 1.0),
-            ),
-            (
-                "wakingFront".into(),
-                CharacterAnimation::new(18, 6, false, 1.0),
-            ),
-            (
-                "wakingLeft".into(),
-                CharacterAnimation::new(24, 6, false, 1.0),
-            ),
-            (
-                "wakingRight".into(),
-                CharacterAnimation::new(24, 6, true, 1.0),
-            ),
-            (
-                "wakingBack".into(),
-                CharacterAnimation::new(30, 6, false, 1.0),
-            ),
-            (
-                "attackingFront".into(),
-                CharacterAnimation::new(36, 4, false, 0.4),
-            ),
-            (
-                "attackingLeft".into(),
-                CharacterAnimation::new(42, 4, false, 0.4),
-            ),
-            (
-                "attackingRight".into(),
-                CharacterAnimation::new(42, 4, true, 0.4),
-            ),
-            (
-                "attackingBack".into(),
-                CharacterAnimation::new(48, 4, true, 0.4),
-            ),
-        ]
-        .into_iter()
-        .collect();
-        let variables = vec![
-            Variable::Enum("none".into()),
-            Variable::Enum("down".into()),
-            Variable::Bool(false),
-            Variable::Bool(false),
-        ];
-
-        let name_to_variable = vec![
-            ("directionX".into(), 0),
-            ("directionY".into(), 1),
-            ("walking".into(), 2),
-            ("attacking".into(), 3),
-        ]
-        .into_iter()
-        .collect();
-
-        let nodes = vec![
-            NodeType::Root(1),
-            NodeType::Switch {
-                variables: vec![0, 1, 2, 3],
-                cases: vec![
-                    vec![
-                        Variable::Enum("none".into()),
-                        Variable::Enum("down".into()),
-                        Variable::Bool(false),
-                        Variable::Bool(false),
-                    ],
-                    vec![
-                        Variable::Enum("none".into()),
-                        Variable::Enum("up".into()),
-                        Variable::Bool(false),
-                        Variable::Bool(false),
-                    ],
-                    vec![
-                        Variable::Enum("left".into()),
-                        Variable::Enum("up".into()),
-                        Variable::Bool(false),
-                        Variable::Bool(false),
-                    ],
-                    vec![
-                        Variable::Enum("left".into()),
-                        Variable::Enum("down".into()),
-                        Variable::Bool(false),
-                        Variable::Bool(false),
-                    ],
-                    vec![
-                        Variable::Enum("right".into()),
-                        Variable::Enum("up".into()),
-                        Variable::Bool(false),
-                        Variable::Bool(false),
-                    ],
-                    vec![
-                        Variable::Enum("right".into()),
-                        Variable::Enum("down".into()),
-                        Variable::Bool(false),
-                        Variable::Bool(false),
-                    ],
-                    vec![
-                        Variable::Enum("none".into()),
-                        Variable::Enum("down".into()),
-                        Variable::Bool(true),
-                        Variable::Bool(false),
-                    ],
-                    vec![
-                        Variable::Enum("none".into()),
-                        Variable::Enum("up".into()),
-                        Variable::Bool(true),
-                        Variable::Bool(false),
-                    ],
-                    vec![
-                        Variable::Enum("left".into()),
-                        Variable::Enum("up".into()),
-                        Variable::Bool(true),
-                        Variable::Bool(false),
-                    ],
-                    vec![
-                        Variable::Enum("left".into()),
-                        Variable::Enum("down".into()),
-                        Variable::Bool(true),
-                        Variable::Bool(false),
-                    ],
-                    vec![
-                        Variable::Enum("right".into()),
-                        Variable::Enum("up".into()),
-                        Variable::Bool(true),
-                        Variable::Bool(false),
-                    ],
-                    vec![
-                        Variable::Enum("right".into()),
-                        Variable::Enum("down".into()),
-                        Variable::Bool(true),
-                        Variable::Bool(false),
-                    ],
-                    // Attacking
-                    vec![
-                        Variable::Enum("none".into()),
-                        Variable::Enum("down".into()),
-                        Variable::Any,
-                        Variable::Bool(true),
-                    ],
-                    vec![
-                        Variable::Enum("none".into()),
-                        Variable::Enum("up".into()),
-                        Variable::Any,
-                        Variable::Bool(true),
-                    ],
-                    vec![
-                        Variable::Enum("left".into()),
-                        Variable::Any,
-                        Variable::Any,
-                        Variable::Bool(true),
-                    ],
-                    vec![
-                        Variable::Enum("right".into()),
-                        Variable::Any,
-                        Variable::Any,
-                        Variable::Bool(true),
-                    ],
-                ],
-                result: vec![2, 5, 3, 3, 4, 4, 6, 9, 7, 7, 8, 8, 10, 13, 11, 12],
-            },
-            NodeType::State("standingFront".into(), 1, false),
-            NodeType::State("standingLeft".into(), 1, false),
-            NodeType::State("standingRight".into(), 1, false),
-            NodeType::State("standingBack".into(), 1, false),
-            NodeType::State("wakingFront".into(), 1, false),
-            NodeType::State("wakingLeft".into(), 1, false),
-            NodeType::State("wakingRight".into(), 1, false),
-            NodeType::State("wakingBack".into(), 1, false),
-            NodeType::State("attackingFront".into(), 14, true),
-            NodeType::State("attackingLeft".into(), 14, true),
-            NodeType::State("attackingRight".into(), 14, true),
-            NodeType::State("attackingBack".into(), 14, true),
-            NodeType::Setter(vec![3], vec![Variable::Bool(false)], 1), // Set attacking false and re-eval
-        ];
+    // Index of the numeric variable `name`, for binding the speed to it.
+    fn speed_variable_index(&self, name: &str) -> Result<usize> {
+        match self.name_to_variable.get(name) {
+            Some(index)
+                if matches!(
+                    self.domains[*index],
+                    VariableDomain::Int | VariableDomain::Float
+                ) =>
+            {
+                Ok(*index)
+            }
+            Some(_) => Err(GameError::new(format!(
+                "speed can not be bound to `{name}` which is not a number"
+            ))),
+            None => Err(GameError::new(format!("unknown variable `{name}`"))),
+        }
+    }
+
+    // Formats values of a case like `directionX = left, directionY = *`.
+    fn describe_values(&self, variables: &[usize], values: &[Variable]) -> String {
+        variables
+            .iter()
+            .zip(values)
+            .map(|(v, value)| format!("{} = {}", self.variable_name(*v), value))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn variable_name(&self, index: usize) -> &str {
+        self.name_to_variable
+            .iter()
+            .find(|(_, v)| **v == index)
+            .map(|(name, _)| name.as_ref())
+            .unwrap_or("?")
+    }
+}
 
+impl CharacterAnimationGraph {
+    /// Starts playing `definition` from the root with the default values of its variables.
+    pub fn new(definition: Arc<AnimationGraphDefinition>) -> Self {
         CharacterAnimationGraph {
-            animations,
-            variables,
-            name_to_variable,
-            nodes,
+            variables: definition.defaults.clone(),
+            revisions: vec![0; definition.defaults.len()],
+            speed_variable: definition.speed_variable,
+            definition,
             current_node: 0,
-            previous_node: 1,
-            next_frame_index: 0,
+            previous_node: 0,
+            state_node: 0,
+            next_frame_index: None,
+            playing_backwards: false,
+            current_frame: None,
+            fired_events: Vec::new(),
             timer: Timer::new(Duration::from_secs_f32(1. / 5.), TimerMode::Once),
+            speed: 1.0,
+            paused: false,
+            playing_transition: false,
+            entry_phase: None,
+            entry_offset: Duration::ZERO,
             reseted: false,
+            pending_priority: 0,
+            unmatched_switch: None,
+            transitions: Default::default(),
+        }
+    }
+
+    pub fn definition(&self) -> &Arc<AnimationGraphDefinition> {
+        &self.definition
+    }
+
+    pub fn get_current_animation(&self) -> Option<&CharacterAnimation> {
+        let current_node = &self.definition.nodes[self.current_node];
+        match current_node {
+            NodeType::State(x, ..) => match &self.current_frame {
+                Some((animation, _)) if self.state_node == self.current_node => {
+                    self.definition.animations.get(animation)
+                }
+                _ => self.definition.animations.get(&self.resolve_animation(x)),
+            },
+            _ => None,
         }
     }
 
+    // The animation a state plays, a directional clip picks the variant of the current direction.
+    fn resolve_animation(&self, name: &Arc<str>) -> Arc<str> {
+        self.definition
+            .directional
+            .get(name)
+            .and_then(|clip| clip.variant(&self.variables[clip.variable]))
+            .unwrap_or(name)
+            .clone()
+    }
+
+    pub fn from_file(path: impl Into<PathBuf>) -> Result<CharacterAnimationGraph> {
+        GraphReader::from_file(path)?.into_graph()
+    }
+
+    /// Replaces the definition of the graph with `definition` while keeping the running state.
+    /// Variables and nodes are matched by name, everything that no longer exists starts from the new defaults.
+    pub fn reload(&mut self, definition: &Arc<AnimationGraphDefinition>) {
+        let mut reloaded = CharacterAnimationGraph::new(definition.clone());
+
+        for (name, index) in &self.definition.name_to_variable {
+            if let Some(new_index) = definition.name_to_variable.get(name) {
+                let value = &self.variables[*index];
+                if definition.domains[*new_index].contains(value) {
+                    reloaded.variables[*new_index] = value.clone();
+                    reloaded.revisions[*new_index] = self.revisions[*index];
+                }
+            }
+        }
+
+        let find_node = |index: usize| {
+            let name = &self.definition.node_names[index];
+            definition.node_names.iter().position(|x| x == name)
+        };
+        match (
+            find_node(self.current_node),
+            find_node(self.previous_node),
+            find_node(self.state_node),
+        ) {
+            (Some(current_node), Some(previous_node), Some(state_node)) => {
+                reloaded.current_node = current_node;
+                reloaded.previous_node = previous_node;
+                reloaded.state_node = state_node;
+
+                // The clip on screen may have been shortened or removed, its frame is only kept if it still exists.
+                reloaded.current_frame = self.current_frame.as_ref().and_then(|(name, frame)| {
+                    let (name, animation) = definition.animations.get_key_value(name)?;
+                    (*frame < animation.count).then(|| (name.clone(), *frame))
+                });
+                let count = reloaded
+                    .current_frame
+                    .as_ref()
+                    .map(|(name, _)| definition.animations[name].count);
+                reloaded.next_frame_index = self
+                    .next_frame_index
+                    .filter(|index| count.is_some_and(|count| *index < count));
+                if reloaded.next_frame_index.is_some() {
+                    reloaded.playing_backwards = self.playing_backwards;
+                    reloaded.playing_transition = self.playing_transition;
+                }
+
+                let duration = reloaded
+                    .current_frame_duration()
+                    .unwrap_or(self.timer.duration());
+                reloaded.timer = Timer::new(duration, TimerMode::Once);
+                reloaded.timer.tick(self.timer.elapsed().min(duration));
+                reloaded.entry_offset = self.entry_offset.min(duration);
+                reloaded.reseted = self.reseted;
+                reloaded.pending_priority = self.pending_priority;
+            }
+            _ => {
+                // The node we were in is gone, start over from the root.
+                reloaded.reseted = true;
+            }
+        }
+
+        reloaded.speed = self.speed;
+        reloaded.paused = self.paused;
+        if let Some(index) = self.speed_variable {
+            let name = self.definition.variable_name(index);
+            reloaded.speed_variable = definition.name_to_variable.get(name).copied();
+        }
+        reloaded.transitions = std::mem::take(&mut self.transitions);
+
+        *self = reloaded;
+    }
+
     pub fn set_variable(&mut self, name: impl AsRef<str>, value: Variable) {
-        if value.is_any() {
-            warn!("cannot set a variable to ANY. Skipping...");
+        self.set_variable_with_priority(name, value, 0);
+    }
+
+    /// Sets a variable like [`Self::set_variable`], but the change interrupts states with a priority up to `priority`
+    /// right away instead of waiting for them to finish, for example to cut into an attack when getting hurt.
+    pub fn set_variable_with_priority(
+        &mut self,
+        name: impl AsRef<str>,
+        value: Variable,
+        priority: u32,
+    ) {
+        if value.is_pattern() {
+            warn!("cannot set a variable to a pattern. Skipping...");
             return;
         }
         let name = name.as_ref();
 
-        if let Some(v) = self.name_to_variable.get(name) {
+        if let Some((name, v)) = self.definition.name_to_variable.get_key_value(name) {
+            if !self.definition.domains[*v].contains(&value) {
+                warn!("`{value}` is not a valid value for `{name}`. Skipping...");
+                return;
+            }
             if self.variables[*v] != value {
                 self.variables[*v] = value;
+                self.revisions[*v] = self.revisions[*v].wrapping_add(1);
                 self.reseted = true;
+                self.pending_priority = self.pending_priority.max(priority);
+                self.unmatched_switch = None;
+                self.transitions.variable_changed(name);
             }
         }
     }
 
-    fn eval(&mut self) -> Option<usize> {
+    fn eval(&mut self) -> Result<Option<AnimationFrame>> {
         loop {
-            let current_node = &self.nodes[self.current_node];
+            let current_node = &self.definition.nodes[self.current_node];
             match current_node {
                 NodeType::Root(x) => {
+                    // The root evaluates every variable again, so changes that waited for a state to finish
+                    // are applied here instead of restarting the graph a second time.
+                    let cause = if self.reseted {
+                        self.reseted = false;
+                        self.pending_priority = 0;
+                        TransitionCause::VariablesChanged(self.transitions.take_changed())
+                    } else {
+                        TransitionCause::Root
+                    };
+                    self.transitions.record(
+                        &self.definition.node_names,
+                        self.current_node,
+                        *x,
+                        cause,
+                    );
                     self.previous_node = self.current_node;
                     self.current_node = *x;
                     continue;
                 }
-                NodeType::State(x, y, _) => {
-                    let state = self.animations.get(x).unwrap();
+                NodeType::SubGraph(x) => {
+                    self.transitions.record(
+                        &self.definition.node_names,
+                        self.current_node,
+                        *x,
+                        TransitionCause::SubGraph,
+                    );
+                    self.current_node = *x;
+                }
+                NodeType::State(x, y, _, entry) => {
+                    let from_other_state =
+                        self.current_frame.is_some() && self.state_node != self.current_node;
+                    // The variant of a directional clip is picked when the state starts and kept until it is left.
+                    let x = &match (self.next_frame_index, &self.current_frame) {
+                        (Some(_), Some((animation, _))) => animation.clone(),
+                        _ => match &entry.transition {
+                            Some(transition) if from_other_state => {
+                                self.playing_transition = true;
+                                self.resolve_animation(transition)
+                            }
+                            _ => self.resolve_animation(x),
+                        },
+                    };
+                    let state = self.definition.animations.get(x).unwrap();
+                    self.state_node = self.current_node;
+                    self.unmatched_switch = None;
 
-                    // If the current frame is 0 we reset it to were it should be.
-                    if self.next_frame_index < state.start {
-                        self.next_frame_index = state.start;
-                    }
+                    let current_frame = match self.next_frame_index {
+                        Some(frame) => frame,
+                        None => {
+                            self.playing_backwards = false;
+                            match self.entry_phase.take() {
+                                Some(phase) if entry.sync && !self.playing_transition => {
+                                    let (frame, shown) = state.frame_at(phase);
+                                    self.entry_offset =
+                                        Duration::try_from_secs_f32(shown).unwrap_or_default();
+                                    frame
+                                }
+                                _ => state.playback.first_frame(state.count),
+                            }
+                        }
+                    };
 
-                    let current_frame = self.next_frame_index;
-                    let start_frame = state.start;
-                    let count = state.count;
+                    // A held frame fires its events only the first time it is shown.
+                    let held = state.playback == Playback::Hold
+                        && self.current_frame.as_ref() == Some(&(x.clone(), current_frame));
+                    if !held {
+                        for (frame, event) in &state.events {
+                            if *frame == current_frame {
+                                self.fired_events.push((x.clone(), event.clone()));
+                            }
+                        }
+                    }
+                    self.current_frame = Some((x.clone(), current_frame));
 
-                    self.next_frame_index = current_frame + 1;
+                    self.next_frame_index = state.playback.next_frame(
+                        current_frame,
+                        state.count,
+                        &mut self.playing_backwards,
+                    );
 
                     // Animation finished moving to next node
-                    if self.next_frame_index > start_frame + count - 1 {
-                        self.next_frame_index = 0;
-                        self.current_node = *y;
+                    if self.next_frame_index.is_none() {
+                        if self.playing_transition {
+                            // The animation of the state follows its transition clip.
+                            self.playing_transition = false;
+                        } else {
+                            self.transitions.record(
+                                &self.definition.node_names,
+                                self.current_node,
+                                *y,
+                                TransitionCause::Finished,
+                            );
+                            self.current_node = *y;
+                        }
                     }
-                    return Some(current_frame);
+                    return Ok(Some(AnimationFrame {
+                        index: state.start + current_frame,
+                        flip_x: state.flip_x,
+                    }));
                 }
                 NodeType::Switch {
                     variables,
                     cases,
                     result,
+                    default,
                 } => {
                     let state = variables
                         .iter()
@@ -309,62 +480,352 @@ impl CharacterAnimationGraph {
                         .collect::<Vec<_>>();
                     let evaluation = cases
                         .iter()
-                        .position(|case| case.iter().zip(state.iter()).all(|(x, y)| &x == y));
+                        .position(|case| case.iter().zip(state.iter()).all(|(x, y)| x.matches(y)));
 
                     if let Some(position) = evaluation {
+                        if self.transitions.is_enabled() {
+                            let case = self.definition.describe_values(variables, &cases[position]);
+                            self.transitions.record(
+                                &self.definition.node_names,
+                                self.current_node,
+                                result[position],
+                                TransitionCause::Case(case),
+                            );
+                        }
+                        consume_triggers(&mut self.variables, variables, &cases[position]);
                         let position = result[position];
                         self.current_node = position;
+                    } else if let Some(default) = default {
+                        self.transitions.record(
+                            &self.definition.node_names,
+                            self.current_node,
+                            *default,
+                            TransitionCause::Default,
+                        );
+                        self.current_node = *default;
                     } else {
-                        panic!("graph switch evaluation failed.\nState: {:?}", state);
+                        // The graph keeps trying every frame, the same failure is only reported until
+                        // a variable changes or a state is reached, so it does not flood the log.
+                        let repeated = self.unmatched_switch.replace(self.current_node)
+                            == Some(self.current_node);
+                        if repeated {
+                            self.current_node = 0;
+                            return Ok(None);
+                        }
+
+                        let state = variables
+                            .iter()
+                            .map(|v| {
+                                format!(
+                                    "{} = {}",
+                                    self.definition.variable_name(*v),
+                                    self.variables[*v]
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        let error = GameError::new(format!(
+                            "switch `{}` has no case for {}",
+                            self.definition.node_names[self.current_node], state
+                        ));
+                        // Start over from the root so that a later variable change can recover.
+                        self.transitions.record(
+                            &self.definition.node_names,
+                            self.current_node,
+                            0,
+                            TransitionCause::NoCase,
+                        );
+                        self.current_node = 0;
+                        return Err(error);
                     }
                 }
+                NodeType::Condition {
+                    variables,
+                    values,
+                    success,
+                    failure,
+                } => {
+                    let matches = variables
+                        .iter()
+                        .zip(values.iter())
+                        .all(|(v, value)| value.matches(&self.variables[*v]));
+
+                    let next = if matches {
+                        consume_triggers(&mut self.variables, variables, values);
+                        *success
+                    } else {
+                        *failure
+                    };
+                    self.transitions.record(
+                        &self.definition.node_names,
+                        self.current_node,
+                        next,
+                        TransitionCause::Condition(matches),
+                    );
+                    self.current_node = next;
+                }
                 NodeType::Setter(variables, values, next) => {
                     for (index, variable_index) in variables.iter().enumerate() {
                         if let Some(variable) = self.variables.get_mut(*variable_index) {
                             if let Some(new_val) = values.get(index) {
-                                if new_val.is_any() {
-                                    warn!("cannot set a variable to ANY. Skipping...");
+                                if new_val.is_pattern() {
+                                    warn!("cannot set a variable to a pattern. Skipping...");
                                     break;
                                 }
                                 // TODO: Check for any, we should not allow it.
-                                *variable = new_val.clone();
+                                if variable != new_val {
+                                    *variable = new_val.clone();
+                                    self.revisions[*variable_index] =
+                                        self.revisions[*variable_index].wrapping_add(1);
+                                }
                             }
                         }
                     }
+                    self.transitions.record(
+                        &self.definition.node_names,
+                        self.current_node,
+                        *next,
+                        TransitionCause::Setter,
+                    );
                     self.current_node = *next;
                 }
             }
         }
     }
 
-    pub fn get_next_index(&mut self, delta: f32) -> Option<usize> {
-        let current_node = &self.nodes[self.current_node];
+    /// Advances the graph by `delta` seconds and returns the frame to show, if it changed.
+    pub fn get_next_index(&mut self, delta: f32) -> Result<Option<AnimationFrame>> {
+        if self.paused {
+            return Ok(None);
+        }
+        let delta = Duration::try_from_secs_f32(delta * self.effective_speed()).unwrap_or_default();
+        let mut over;
 
-        if self.reseted && !current_node.is_locking() {
+        // The priority of the state on screen, which keeps its last frame even though the graph has moved on.
+        let mut result = if self.reseted && self.pending_priority >= self.priority() {
+            let changed = self.transitions.take_changed();
+            self.transitions.record(
+                &self.definition.node_names,
+                self.current_node,
+                0,
+                TransitionCause::VariablesChanged(changed),
+            );
             self.current_node = 0; //Root Node
-            self.next_frame_index = 0;
+            self.next_frame_index = None;
+            self.playing_transition = false;
             self.reseted = false;
-            return self.eval();
+            self.pending_priority = 0;
+
+            // States that start in sync continue from where the interrupted animation was.
+            self.entry_phase = self.progress();
+            let result = self.eval();
+            self.entry_phase = None;
+            over = std::mem::take(&mut self.entry_offset);
+            result?
+        } else {
+            // A once timer stops at its duration, so the time we overshoot has to be calculated before ticking.
+            over = delta.saturating_sub(self.timer.remaining());
+            self.timer.tick(delta);
+            if !self.timer.finished() {
+                return Ok(None);
+            }
+            self.eval()?
+        };
+
+        // Add the overshoot time to the next timer. This way the timings are going to be more accurate.
+        // When the overshoot is longer than the frame, the frame is skipped.
+        while let Some(duration) = self.current_frame_duration() {
+            if over < duration || duration.is_zero() {
+                self.timer = Timer::new(duration, TimerMode::Once);
+                self.timer.tick(over);
+                break;
+            }
+            over -= duration;
+            result = self.eval()?;
+        }
+
+        Ok(result)
+    }
+
+    // How long the frame on screen should be shown for.
+    fn current_frame_duration(&self) -> Option<Duration> {
+        let (animation, frame) = self.current_frame.as_ref()?;
+        let animation = self.definition.animations.get(animation)?;
+        // Validation rejects durations that are not positive, this keeps a bad one from panicking.
+        Duration::try_from_secs_f32(animation.frame_duration(*frame)).ok()
+    }
+
+    /// Every variable of the graph with its current value.
+    pub fn variables(&self) -> impl Iterator<Item = (&Arc<str>, &Variable)> {
+        self.definition
+            .name_to_variable
+            .iter()
+            .map(|(name, index)| (name, &self.variables[*index]))
+    }
+
+    // The variables with how often they were set to a new value, for layers that copy the changes.
+    fn variable_revisions(&self) -> impl Iterator<Item = (&Arc<str>, &Variable, u32)> {
+        self.definition
+            .name_to_variable
+            .iter()
+            .map(|(name, index)| (name, &self.variables[*index], self.revisions[*index]))
+    }
+
+    pub fn has_variable(&self, name: impl AsRef<str>) -> bool {
+        self.definition.name_to_variable.contains_key(name.as_ref())
+    }
+
+    pub fn variable(&self, name: impl AsRef<str>) -> Option<&Variable> {
+        let index = self.definition.name_to_variable.get(name.as_ref())?;
+        self.variables.get(*index)
+    }
+
+    /// Name of the state whose animation is on screen, `None` before the first frame.
+    pub fn current_state(&self) -> Option<&str> {
+        self.current_frame.as_ref()?;
+        Some(&self.definition.node_names[self.state_node])
+    }
+
+    /// Whether variable changes without a priority wait until the state on screen is done, for example during an attack.
+    pub fn is_locking(&self) -> bool {
+        self.definition.nodes[self.node_on_screen()].is_locking()
+    }
+
+    /// The priority a variable change needs to interrupt the state on screen.
+    pub fn priority(&self) -> u32 {
+        self.definition.nodes[self.node_on_screen()].priority()
+    }
+
+    // While the last frame of a state is shown the graph has already moved on to the next node,
+    // the state still owns the screen until that frame is done.
+    fn node_on_screen(&self) -> usize {
+        match self.current_frame {
+            Some(_) => self.state_node,
+            None => self.current_node,
+        }
+    }
+
+    /// The highest priority of the variable changes that wait for the current node to finish.
+    pub fn pending_priority(&self) -> Option<u32> {
+        self.reseted.then_some(self.pending_priority)
+    }
+
+    /// How far the animation on screen is, from 0.0 when it starts to 1.0 when its last frame is done.
+    /// Reversed animations start from their last frame, ping pong animations report where the frame is in the clip.
+    pub fn progress(&self) -> Option<f32> {
+        let (animation, frame) = self.current_frame.as_ref()?;
+        let animation = self.definition.animations.get(animation)?;
+
+        let played = if animation.playback.is_reversed() {
+            frame + 1..animation.count
+        } else {
+            0..*frame
+        };
+        let before = played.map(|x| animation.frame_duration(x)).sum::<f32>();
+        let shown = self
+            .timer
+            .elapsed_secs()
+            .min(animation.frame_duration(*frame));
+        let total = (0..animation.count)
+            .map(|x| animation.frame_duration(x))
+            .sum::<f32>();
+        if total <= 0.0 {
+            return Some(1.0);
         }
-        self.timer.tick(Duration::from_secs_f32(delta));
-        if !self.timer.finished() {
+        Some(((before + shown) / total).clamp(0.0, 1.0))
+    }
+
+    /// Frames of the animation on screen that are still to be shown, `None` when it never finishes.
+    pub fn frames_remaining(&self) -> Option<usize> {
+        let (animation, _) = self.current_frame.as_ref()?;
+        let animation = self.definition.animations.get(animation)?;
+        if !animation.playback.finishes() {
             return None;
         }
 
-        let result = self.eval();
+        let mut backwards = self.playing_backwards;
+        let mut next = self.next_frame_index;
+        let mut remaining = 0;
+        while let Some(frame) = next {
+            remaining += 1;
+            next = animation
+                .playback
+                .next_frame(frame, animation.count, &mut backwards);
+        }
+        Some(remaining)
+    }
 
-        if let Some(animation) = self.get_current_animation() {
-            // Calculate the time we overshoot.
-            let over = self.timer.elapsed() - self.timer.duration();
-            self.timer = Timer::new(
-                Duration::from_secs_f32(animation.animation_duration / animation.count as f32),
-                TimerMode::Once,
-            );
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
 
-            // Add the overshoot time to the next timer. This way the timings are going to be more accurate.
-            self.timer.tick(over);
+    /// Scales how fast the animations play, 1.0 being their normal speed, up to [`MAX_SPEED`].
+    pub fn set_speed(&mut self, speed: f32) {
+        if !speed.is_finite() {
+            warn!("cannot set the speed to {speed}. Skipping...");
+            return;
         }
+        self.speed = speed.clamp(0.0, MAX_SPEED);
+    }
+
+    /// Multiplies the speed with the value of a numeric variable every frame.
+    pub fn bind_speed(&mut self, name: impl AsRef<str>) -> Result<()> {
+        self.speed_variable = Some(self.definition.speed_variable_index(name.as_ref())?);
+        Ok(())
+    }
 
-        result
+    pub fn unbind_speed(&mut self) {
+        self.speed_variable = None;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn effective_speed(&self) -> f32 {
+        let scale = match self.speed_variable.map(|x| &self.variables[x]) {
+            Some(Variable::Float(x)) => *x,
+            Some(Variable::Int(x)) => *x as f32,
+            _ => 1.0,
+        };
+        // A speed that is not a number stops the animation instead of panicking when the timer is ticked.
+        let speed = self.speed * scale;
+        if speed.is_nan() {
+            0.0
+        } else {
+            speed.clamp(0.0, MAX_SPEED)
+        }
+    }
+
+    /// Takes the events fired by the frames returned from [`Self::get_next_index`] since the last call.
+    pub fn drain_events(&mut self) -> std::vec::Drain<'_, (Arc<str>, Arc<str>)> {
+        self.fired_events.drain(..)
+    }
+
+    /// Keeps the last `capacity` transitions of the graph, 0 turns the log off.
+    pub fn log_transitions(&mut self, capacity: usize) {
+        self.transitions.set_capacity(capacity);
+    }
+
+    /// The logged transitions, oldest first.
+    pub fn transitions(&self) -> impl Iterator<Item = &Transition> {
+        self.transitions.iter()
+    }
+}
+
+// A trigger that a transition waited for is switched off again, so it only fires once.
+fn consume_triggers(variables: &mut [Variable], indices: &[usize], values: &[Variable]) {
+    for (index, value) in indices.iter().zip(values) {
+        if matches!(value, Variable::Trigger(true)) {
+            variables[*index] = Variable::Trigger(false);
+        }
     }
 }
@@ -2,24 +2,70 @@ use std::sync::Arc;
 
 use crate::prelude::*;
 
-#[derive(Debug)]
+/// How a state starts when the graph moves to it.
+#[derive(Debug, Clone, Default)]
+pub struct StateEntry {
+    pub sync: bool, // start at the same point of the animation as the one that was interrupted
+    pub transition: Option<Arc<str>>, // animation played once before the one of the state when coming from another state
+}
+
+#[derive(Debug, Clone)]
 pub enum NodeType {
-    Root(usize),                  // index of the first node
-    State(Arc<str>, usize, bool), // name of state and index of the next node, the node is locking or not base on the last bool
+    Root(usize),                             // index of the first node
+    State(Arc<str>, usize, u32, StateEntry), // name of state, index of the next node, the priority a variable change needs to interrupt it and how it starts
     Switch {
         variables: Vec<usize>,     // array on indicies in the variables vector
         cases: Vec<Vec<Variable>>, // index of variable value to check against and the values that they should have in order to return true
         result: Vec<usize>,        // the index of the node we should go if we return true.
+        default: Option<usize>,    // the index of the node we go to when no case matches.
+    },
+    Condition {
+        variables: Vec<usize>, // array on indicies in the variables vector
+        values: Vec<Variable>, // the values the variables should have for the condition to succeed
+        success: usize,        // the index of the node we go to when all the variables match
+        failure: usize,        // the index of the node we go to otherwise
     },
     Setter(Vec<usize>, Vec<Variable>, usize), // Set the variables to the values defined
+    SubGraph(usize),                          // index of the entry node of an embedded graph
 }
 
 impl NodeType {
     pub fn is_locking(&self) -> bool {
+        self.priority() > 0
+    }
+
+    /// Variable changes with a lower priority wait until the node is done.
+    pub fn priority(&self) -> u32 {
+        match self {
+            NodeType::State(_, _, priority, _) => *priority,
+            NodeType::Setter(..) => u32::MAX,
+            _ => 0,
+        }
+    }
+
+    /// Every node this node can move to.
+    pub fn next_nodes(&self) -> Vec<usize> {
+        match self {
+            NodeType::Root(next)
+            | NodeType::State(_, next, ..)
+            | NodeType::Setter(_, _, next)
+            | NodeType::SubGraph(next) => vec![*next],
+            NodeType::Switch {
+                result, default, ..
+            } => result.iter().chain(default.iter()).copied().collect(),
+            NodeType::Condition {
+                success, failure, ..
+            } => vec![*success, *failure],
+        }
+    }
+
+    /// The variables this node reads or writes.
+    pub fn variables(&self) -> &[usize] {
         match self {
-            NodeType::State(_, _, locking) => *locking,
-            NodeType::Setter(..) => true,
-            _ => false,
+            NodeType::Root(_) | NodeType::State(..) | NodeType::SubGraph(_) => &[],
+            NodeType::Switch { variables, .. }
+            | NodeType::Condition { variables, .. }
+            | NodeType::Setter(variables, _, _) => variables,
         }
     }
 }
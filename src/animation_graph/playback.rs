@@ -0,0 +1,62 @@
+use serde::Deserialize;
+
+/// How the frames of an animation are played.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Playback {
+    #[default]
+    Once, // play forward and move to the next node
+    Loop,            // play forward forever
+    Hold,            // play forward and stay on the last frame
+    PingPong,        // play forward then backward forever
+    Reverse,         // play backward and move to the next node
+    ReverseLoop,     // play backward forever
+    PingPongReverse, // play backward then forward forever
+}
+
+impl Playback {
+    /// Whether the animation ever finishes and moves on to the next node.
+    pub fn finishes(&self) -> bool {
+        matches!(self, Playback::Once | Playback::Reverse)
+    }
+
+    /// Whether the animation starts from its last frame.
+    pub fn is_reversed(&self) -> bool {
+        matches!(
+            self,
+            Playback::Reverse | Playback::ReverseLoop | Playback::PingPongReverse
+        )
+    }
+
+    pub fn first_frame(&self, count: usize) -> usize {
+        if self.is_reversed() {
+            count.saturating_sub(1)
+        } else {
+            0
+        }
+    }
+
+    /// The frame shown after `frame`, `None` once the animation is finished.
+    /// `backwards` keeps the direction of a ping pong between calls.
+    pub fn next_frame(&self, frame: usize, count: usize, backwards: &mut bool) -> Option<usize> {
+        let last = count.saturating_sub(1);
+        match self {
+            Playback::Once => (frame < last).then_some(frame + 1),
+            Playback::Reverse => frame.checked_sub(1),
+            Playback::ReverseLoop => Some(frame.checked_sub(1).unwrap_or(last)),
+            Playback::Loop => Some(if frame < last { frame + 1 } else { 0 }),
+            Playback::Hold => Some(last.min(frame + 1)),
+            Playback::PingPong | Playback::PingPongReverse => {
+                if last == 0 {
+                    return Some(0);
+                }
+                if frame == last {
+                    *backwards = true;
+                } else if frame == 0 {
+                    *backwards = false;
+                }
+                Some(if *backwards { frame - 1 } else { frame + 1 })
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::GameError, prelude::*};
+
+use super::MAX_SPEED;
+
+/// The running state of a [`CharacterAnimationGraph`], for save games and rollback.
+/// Nodes and variables are stored by name, so a snapshot can be restored on any instance of the same graph.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnimationGraphSnapshot {
+    pub current_node: String,
+    pub previous_node: String,
+    // Empty in snapshots taken before it was stored, the current node is used instead.
+    #[serde(default)]
+    pub state_node: String,
+    pub next_frame_index: Option<usize>,
+    pub playing_backwards: bool,
+    #[serde(default)]
+    pub playing_transition: bool,
+    pub current_frame: Option<(String, usize)>,
+    pub variables: BTreeMap<String, Variable>,
+    pub timer_duration: Duration,
+    pub timer_elapsed: Duration,
+    pub speed: f32,
+    pub speed_variable: Option<String>,
+    pub paused: bool,
+    pub reseted: bool,
+    #[serde(default)]
+    pub pending_priority: u32,
+}
+
+impl CharacterAnimationGraph {
+    pub fn snapshot(&self) -> AnimationGraphSnapshot {
+        AnimationGraphSnapshot {
+            current_node: self.definition.node_names[self.current_node].to_string(),
+            previous_node: self.definition.node_names[self.previous_node].to_string(),
+            state_node: self.definition.node_names[self.state_node].to_string(),
+            next_frame_index: self.next_frame_index,
+            playing_backwards: self.playing_backwards,
+            playing_transition: self.playing_transition,
+            current_frame: self
+                .current_frame
+                .as_ref()
+                .map(|(animation, frame)| (animation.to_string(), *frame)),
+            variables: self
+                .variables()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+            timer_duration: self.timer.duration(),
+            timer_elapsed: self.timer.elapsed(),
+            speed: self.speed,
+            speed_variable: self
+                .speed_variable
+                .map(|index| self.definition.variable_name(index).to_string()),
+            paused: self.paused,
+            reseted: self.reseted,
+            pending_priority: self.pending_priority,
+        }
+    }
+
+    /// Puts the graph back in the state of `snapshot`, so it continues from the same frame.
+    /// Nothing is changed when the snapshot does not fit this graph.
+    pub fn restore(&mut self, snapshot: &AnimationGraphSnapshot) -> Result<()> {
+        let find_node = |name: &str| {
+            self.definition
+                .node_names
+                .iter()
+                .position(|x| x.as_ref() == name)
+                .ok_or_else(|| GameError::new(format!("unknown node `{name}` in snapshot")))
+        };
+        let current_node = find_node(&snapshot.current_node)?;
+        let previous_node = find_node(&snapshot.previous_node)?;
+        let state_node = match snapshot.state_node.as_str() {
+            "" => current_node,
+            name => find_node(name)?,
+        };
+
+        let mut variables = self.variables.clone();
+        for (name, value) in &snapshot.variables {
+            let index = self.variable_index(name)?;
+            if !self.definition.domains[index].contains(value) {
+                return Err(GameError::new(format!(
+                    "`{value}` is not a valid value for `{name}` in snapshot"
+                )));
+            }
+            variables[index] = value.clone();
+        }
+        let speed_variable = snapshot
+            .speed_variable
+            .as_ref()
+            .map(|name| self.definition.speed_variable_index(name))
+            .transpose()?;
+
+        // The graph may have been edited since the snapshot was taken, frames past the end of an
+        // animation would never let it finish.
+        let current_frame = match &snapshot.current_frame {
+            Some((animation, frame)) => {
+                let Some((name, clip)) =
+                    self.definition.animations.get_key_value(animation.as_str())
+                else {
+                    return Err(GameError::new(format!(
+                        "unknown animation `{animation}` in snapshot"
+                    )));
+                };
+                for frame in std::iter::once(frame).chain(&snapshot.next_frame_index) {
+                    if *frame >= clip.count {
+                        return Err(GameError::new(format!(
+                            "frame {frame} of `{animation}` in snapshot but it only has {} frames",
+                            clip.count
+                        )));
+                    }
+                }
+                Some((name.clone(), *frame))
+            }
+            None if snapshot.next_frame_index.is_some() => {
+                return Err(GameError::new(
+                    "snapshot has a next frame but no frame on screen",
+                ))
+            }
+            None => None,
+        };
+
+        if snapshot.timer_elapsed > snapshot.timer_duration {
+            return Err(GameError::new(format!(
+                "timer in snapshot has run for {:?} of {:?}",
+                snapshot.timer_elapsed, snapshot.timer_duration
+            )));
+        }
+        if !(0.0..=MAX_SPEED).contains(&snapshot.speed) {
+            return Err(GameError::new(format!(
+                "speed {} in snapshot is not between 0 and {MAX_SPEED}",
+                snapshot.speed
+            )));
+        }
+
+        let mut timer = Timer::new(snapshot.timer_duration, TimerMode::Once);
+        timer.tick(snapshot.timer_elapsed);
+
+        self.current_node = current_node;
+        self.previous_node = previous_node;
+        self.state_node = state_node;
+        self.next_frame_index = snapshot.next_frame_index;
+        self.playing_backwards = snapshot.playing_backwards;
+        self.playing_transition = snapshot.playing_transition;
+        self.current_frame = current_frame;
+        self.fired_events.clear();
+        // Restored values count as changes, so layers of this graph pick them up.
+        for (index, value) in variables.iter().enumerate() {
+            if *value != self.variables[index] {
+                self.revisions[index] = self.revisions[index].wrapping_add(1);
+            }
+        }
+        self.variables = variables;
+        self.timer = timer;
+        self.speed = snapshot.speed;
+        self.speed_variable = speed_variable;
+        self.paused = snapshot.paused;
+        self.reseted = snapshot.reseted;
+        self.pending_priority = snapshot.pending_priority;
+        Ok(())
+    }
+
+    fn variable_index(&self, name: &str) -> Result<usize> {
+        self.definition
+            .name_to_variable
+            .get(name)
+            .copied()
+            .ok_or_else(|| GameError::new(format!("unknown variable `{name}` in snapshot")))
+    }
+}
@@ -0,0 +1,102 @@
+use std::{collections::VecDeque, fmt::Display, sync::Arc};
+
+/// A move of the graph from one node to another.
+#[derive(Debug, Clone)]
+pub struct Transition {
+    pub from: Arc<str>,
+    pub to: Arc<str>,
+    pub cause: TransitionCause,
+}
+
+#[derive(Debug, Clone)]
+pub enum TransitionCause {
+    VariablesChanged(Vec<Arc<str>>), // the graph restarts from the root because these variables changed
+    Root,
+    Finished,     // the animation of a state finished playing
+    Case(String), // the case of a switch that matched, like `directionX = left, directionY = *`
+    Default,
+    NoCase, // a switch had no case for the variables, the graph starts over
+    Condition(bool),
+    Setter,
+    SubGraph,
+}
+
+impl Display for TransitionCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransitionCause::VariablesChanged(names) => {
+                write!(f, "changed {}", names.join(", "))
+            }
+            TransitionCause::Root => write!(f, "root"),
+            TransitionCause::Finished => write!(f, "animation finished"),
+            TransitionCause::Case(case) => write!(f, "case {case}"),
+            TransitionCause::Default => write!(f, "default"),
+            TransitionCause::NoCase => write!(f, "no case matched"),
+            TransitionCause::Condition(true) => write!(f, "condition succeeded"),
+            TransitionCause::Condition(false) => write!(f, "condition failed"),
+            TransitionCause::Setter => write!(f, "setter"),
+            TransitionCause::SubGraph => write!(f, "entered sub-graph"),
+        }
+    }
+}
+
+/// The last transitions of a graph. Nothing is recorded until a capacity is set, so graphs
+/// that are not being debugged do not pay for it.
+#[derive(Debug, Clone, Default)]
+pub struct TransitionLog {
+    capacity: usize,
+    transitions: VecDeque<Transition>,
+    changed: Vec<Arc<str>>, // variables changed since the graph last restarted
+}
+
+impl TransitionLog {
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.truncate();
+        if capacity == 0 {
+            self.changed.clear();
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Transition> {
+        self.transitions.iter()
+    }
+
+    pub(super) fn variable_changed(&mut self, name: &Arc<str>) {
+        if self.is_enabled() && !self.changed.contains(name) {
+            self.changed.push(name.clone());
+        }
+    }
+
+    pub(super) fn take_changed(&mut self) -> Vec<Arc<str>> {
+        std::mem::take(&mut self.changed)
+    }
+
+    pub(super) fn record(
+        &mut self,
+        names: &[Arc<str>],
+        from: usize,
+        to: usize,
+        cause: TransitionCause,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.transitions.push_back(Transition {
+            from: names[from].clone(),
+            to: names[to].clone(),
+            cause,
+        });
+        self.truncate();
+    }
+
+    fn truncate(&mut self) {
+        while self.transitions.len() > self.capacity {
+            self.transitions.pop_front();
+        }
+    }
+}
@@ -0,0 +1,509 @@
+use std::{fmt::Display, sync::Arc};
+
+use crate::{error::GameError, prelude::*};
+
+/// A problem found by [`AnimationGraphDefinition::validate`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphDiagnostic {
+    NodeOutOfRange {
+        node: Arc<str>,
+        target: usize,
+    },
+    VariableOutOfRange {
+        node: Arc<str>,
+        variable: usize,
+    },
+    CaseLengthMismatch {
+        node: Arc<str>,
+        case: usize,
+        expected: usize,
+        found: usize,
+    },
+    MissingResults {
+        node: Arc<str>,
+        cases: usize,
+        results: usize,
+    },
+    MissingAnimation {
+        node: Arc<str>,
+        animation: Arc<str>,
+    },
+    EmptyAnimation {
+        animation: Arc<str>,
+    },
+    // A duration that is not a positive number of seconds.
+    InvalidDuration {
+        animation: Arc<str>,
+        duration: f32,
+    },
+    // Frame durations that do not match the frame count of the animation or are not positive.
+    InvalidFrameDurations {
+        animation: Arc<str>,
+        count: usize,
+        durations: Vec<f32>,
+    },
+    // An event on a frame the animation does not have, so it never fires.
+    EventOutOfRange {
+        animation: Arc<str>,
+        event: Arc<str>,
+        frame: usize,
+        count: usize,
+    },
+    ValueOutOfDomain {
+        node: Arc<str>,
+        variable: Arc<str>,
+        value: Variable,
+    },
+    // Combinations of variable values that no case of a switch without a default matches.
+    UncoveredCases {
+        node: Arc<str>,
+        variables: Vec<Arc<str>>,
+        combinations: Vec<Vec<Variable>>,
+    },
+    Unreachable {
+        node: Arc<str>,
+    },
+    // A locking state whose animation never finishes, so it can never be left.
+    LockedForever {
+        node: Arc<str>,
+        animation: Arc<str>,
+    },
+    // A transition clip that never finishes, so the animation of its state is never shown.
+    EndlessTransition {
+        node: Arc<str>,
+        animation: Arc<str>,
+    },
+    // Nodes that can move between each other forever without ever reaching a state.
+    Cycle {
+        nodes: Vec<Arc<str>>,
+    },
+}
+
+impl Display for GraphDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphDiagnostic::NodeOutOfRange { node, target } => {
+                write!(f, "`{node}` points to node {target} which does not exist")
+            }
+            GraphDiagnostic::VariableOutOfRange { node, variable } => {
+                write!(f, "`{node}` uses variable {variable} which does not exist")
+            }
+            GraphDiagnostic::CaseLengthMismatch {
+                node,
+                case,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{node}` case {case} has {found} values but the node has {expected} variables"
+            ),
+            GraphDiagnostic::MissingResults {
+                node,
+                cases,
+                results,
+            } => write!(f, "`{node}` has {cases} cases but only {results} results"),
+            GraphDiagnostic::MissingAnimation { node, animation } => {
+                write!(f, "`{node}` plays `{animation}` which is not an animation")
+            }
+            GraphDiagnostic::EmptyAnimation { animation } => {
+                write!(f, "`{animation}` has no frames")
+            }
+            GraphDiagnostic::InvalidDuration {
+                animation,
+                duration,
+            } => write!(
+                f,
+                "`{animation}` lasts {duration} seconds which is not a positive duration"
+            ),
+            GraphDiagnostic::InvalidFrameDurations {
+                animation,
+                count,
+                durations,
+            } => write!(
+                f,
+                "`{animation}` has {count} frames but the frame durations are {durations:?}"
+            ),
+            GraphDiagnostic::EventOutOfRange {
+                animation,
+                event,
+                frame,
+                count,
+            } => write!(
+                f,
+                "`{animation}` fires `{event}` on frame {frame} but only has {count} frames"
+            ),
+            GraphDiagnostic::ValueOutOfDomain {
+                node,
+                variable,
+                value,
+            } => write!(
+                f,
+                "`{node}` uses `{value}` which is not a value of `{variable}`"
+            ),
+            GraphDiagnostic::UncoveredCases {
+                node,
+                variables,
+                combinations,
+            } => {
+                let combinations = combinations
+                    .iter()
+                    .map(|values| {
+                        variables
+                            .iter()
+                            .zip(values)
+                            .map(|(name, value)| format!("{name} = {value}"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, "`{node}` has no case for: {}", combinations.join("; "))
+            }
+            GraphDiagnostic::LockedForever { node, animation } => write!(
+                f,
+                "`{node}` is locking but `{animation}` never finishes playing"
+            ),
+            GraphDiagnostic::EndlessTransition { node, animation } => write!(
+                f,
+                "`{node}` starts with `{animation}` which never finishes playing"
+            ),
+            GraphDiagnostic::Unreachable { node } => {
+                write!(f, "`{node}` can not be reached from the root")
+            }
+            GraphDiagnostic::Cycle { nodes } => {
+                write!(
+                    f,
+                    "nodes loop without reaching a state: {}",
+                    nodes.join(" -> ")
+                )
+            }
+        }
+    }
+}
+
+impl From<Vec<GraphDiagnostic>> for GameError {
+    fn from(value: Vec<GraphDiagnostic>) -> Self {
+        let lines = value.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        GameError(format!("invalid animation graph:\n{}", lines.join("\n")))
+    }
+}
+
+impl AnimationGraphDefinition {
+    /// Checks the graph for mistakes that would otherwise only show up while it is running.
+    pub fn validate(&self) -> std::result::Result<(), Vec<GraphDiagnostic>> {
+        let mut diagnostics = Vec::new();
+
+        for (name, animation) in &self.animations {
+            if animation.count == 0 {
+                diagnostics.push(GraphDiagnostic::EmptyAnimation {
+                    animation: name.clone(),
+                });
+            }
+            if let Some(durations) = &animation.frame_durations {
+                if durations.len() != animation.count || !durations.iter().all(|x| is_duration(*x))
+                {
+                    diagnostics.push(GraphDiagnostic::InvalidFrameDurations {
+                        animation: name.clone(),
+                        count: animation.count,
+                        durations: durations.clone(),
+                    });
+                }
+            } else if !is_duration(animation.animation_duration) {
+                diagnostics.push(GraphDiagnostic::InvalidDuration {
+                    animation: name.clone(),
+                    duration: animation.animation_duration,
+                });
+            }
+            for (frame, event) in &animation.events {
+                if *frame >= animation.count {
+                    diagnostics.push(GraphDiagnostic::EventOutOfRange {
+                        animation: name.clone(),
+                        event: event.clone(),
+                        frame: *frame,
+                        count: animation.count,
+                    });
+                }
+            }
+        }
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let name = self.node_name(index);
+
+            for target in node.next_nodes() {
+                if target >= self.nodes.len() {
+                    diagnostics.push(GraphDiagnostic::NodeOutOfRange {
+                        node: name.clone(),
+                        target,
+                    });
+                }
+            }
+            for variable in node.variables() {
+                if *variable >= self.defaults.len() {
+                    diagnostics.push(GraphDiagnostic::VariableOutOfRange {
+                        node: name.clone(),
+                        variable: *variable,
+                    });
+                }
+            }
+
+            match node {
+                NodeType::State(animation, _, priority, entry) => {
+                    let clips = self.clips_of(animation);
+                    if clips.is_empty() {
+                        diagnostics.push(GraphDiagnostic::MissingAnimation {
+                            node: name.clone(),
+                            animation: animation.clone(),
+                        });
+                    } else if *priority > 0 && clips.iter().any(|x| !x.playback.finishes()) {
+                        diagnostics.push(GraphDiagnostic::LockedForever {
+                            node: name.clone(),
+                            animation: animation.clone(),
+                        });
+                    }
+
+                    if let Some(transition) = &entry.transition {
+                        let clips = self.clips_of(transition);
+                        if clips.is_empty() {
+                            diagnostics.push(GraphDiagnostic::MissingAnimation {
+                                node: name,
+                                animation: transition.clone(),
+                            });
+                        } else if clips.iter().any(|x| !x.playback.finishes()) {
+                            diagnostics.push(GraphDiagnostic::EndlessTransition {
+                                node: name,
+                                animation: transition.clone(),
+                            });
+                        }
+                    }
+                }
+                NodeType::Switch {
+                    variables,
+                    cases,
+                    result,
+                    ..
+                } => {
+                    for (case_index, case) in cases.iter().enumerate() {
+                        if case.len() != variables.len() {
+                            diagnostics.push(GraphDiagnostic::CaseLengthMismatch {
+                                node: name.clone(),
+                                case: case_index,
+                                expected: variables.len(),
+                                found: case.len(),
+                            });
+                        }
+                    }
+                    if result.len() < cases.len() {
+                        diagnostics.push(GraphDiagnostic::MissingResults {
+                            node: name,
+                            cases: cases.len(),
+                            results: result.len(),
+                        });
+                    }
+                }
+                NodeType::Condition {
+                    variables, values, ..
+                }
+                | NodeType::Setter(variables, values, _) => {
+                    if values.len() != variables.len() {
+                        diagnostics.push(GraphDiagnostic::CaseLengthMismatch {
+                            node: name,
+                            case: 0,
+                            expected: variables.len(),
+                            found: values.len(),
+                        });
+                    }
+                }
+                NodeType::Root(_) | NodeType::SubGraph(_) => {}
+            }
+        }
+
+        // Broken indices make the checks below meaningless.
+        if diagnostics.iter().any(|x| {
+            !matches!(
+                x,
+                GraphDiagnostic::MissingAnimation { .. }
+                    | GraphDiagnostic::EmptyAnimation { .. }
+                    | GraphDiagnostic::InvalidDuration { .. }
+                    | GraphDiagnostic::InvalidFrameDurations { .. }
+                    | GraphDiagnostic::EventOutOfRange { .. }
+                    | GraphDiagnostic::LockedForever { .. }
+                    | GraphDiagnostic::EndlessTransition { .. }
+            )
+        }) {
+            return Err(diagnostics);
+        }
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            match node {
+                NodeType::Switch {
+                    variables,
+                    cases,
+                    default,
+                    ..
+                } => {
+                    for case in cases {
+                        self.check_domains(index, variables, case, true, &mut diagnostics);
+                    }
+                    if default.is_none() {
+                        self.check_exhaustive(index, variables, cases, &mut diagnostics);
+                    }
+                }
+                NodeType::Condition {
+                    variables, values, ..
+                } => {
+                    self.check_domains(index, variables, values, true, &mut diagnostics);
+                }
+                NodeType::Setter(variables, values, _) => {
+                    self.check_domains(index, variables, values, false, &mut diagnostics);
+                }
+                _ => {}
+            }
+        }
+
+        let mut reachable = vec![false; self.nodes.len()];
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            if index >= self.nodes.len() || reachable[index] {
+                continue;
+            }
+            reachable[index] = true;
+            stack.extend(self.nodes[index].next_nodes());
+        }
+        for (index, reachable) in reachable.iter().enumerate() {
+            if !reachable {
+                diagnostics.push(GraphDiagnostic::Unreachable {
+                    node: self.node_name(index),
+                });
+            }
+        }
+
+        // Depth first search through the nodes that are evaluated without returning a frame.
+        let mut visited = vec![false; self.nodes.len()];
+        for start in 0..self.nodes.len() {
+            let mut path = Vec::new();
+            self.find_cycle(start, &mut visited, &mut path, &mut diagnostics);
+        }
+
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(diagnostics)
+        }
+    }
+
+    fn find_cycle(
+        &self,
+        index: usize,
+        visited: &mut [bool],
+        path: &mut Vec<usize>,
+        diagnostics: &mut Vec<GraphDiagnostic>,
+    ) {
+        if matches!(self.nodes[index], NodeType::State(..)) {
+            return;
+        }
+        if let Some(position) = path.iter().position(|x| *x == index) {
+            let mut nodes = path[position..]
+                .iter()
+                .map(|x| self.node_name(*x))
+                .collect::<Vec<_>>();
+            nodes.push(self.node_name(index));
+            diagnostics.push(GraphDiagnostic::Cycle { nodes });
+            return;
+        }
+        if visited[index] {
+            return;
+        }
+        visited[index] = true;
+
+        path.push(index);
+        for next in self.nodes[index].next_nodes() {
+            self.find_cycle(next, visited, path, diagnostics);
+        }
+        path.pop();
+    }
+
+    fn check_domains(
+        &self,
+        index: usize,
+        variables: &[usize],
+        values: &[Variable],
+        allow_patterns: bool,
+        diagnostics: &mut Vec<GraphDiagnostic>,
+    ) {
+        for (variable, value) in variables.iter().zip(values) {
+            let value_to_check = match value {
+                Variable::Any if allow_patterns => continue,
+                Variable::Compare(_, x) if allow_patterns => x.as_ref(),
+                x => x,
+            };
+            if !self.domains[*variable].contains(value_to_check) {
+                diagnostics.push(GraphDiagnostic::ValueOutOfDomain {
+                    node: self.node_name(index),
+                    variable: self.variable_name(*variable).into(),
+                    value: value.clone(),
+                });
+            }
+        }
+    }
+
+    // Walks every combination of the switch variables and keeps the ones no case matches.
+    // There are too many numbers to list, a number is only covered by a `*`, so it is listed as one.
+    fn check_exhaustive(
+        &self,
+        index: usize,
+        variables: &[usize],
+        cases: &[Vec<Variable>],
+        diagnostics: &mut Vec<GraphDiagnostic>,
+    ) {
+        let mut combinations = vec![vec![]];
+        for variable in variables {
+            let values = self.domains[*variable]
+                .values()
+                .unwrap_or_else(|| vec![Variable::Any]);
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination: Vec<Variable>| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.push(value.clone());
+                        combination
+                    })
+                })
+                .collect();
+        }
+
+        let uncovered = combinations
+            .into_iter()
+            .filter(|combination| {
+                !cases.iter().any(|case| {
+                    case.iter().zip(combination).all(|(x, y)| match y {
+                        Variable::Any => x.is_any(),
+                        _ => x.matches(y),
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if !uncovered.is_empty() {
+            diagnostics.push(GraphDiagnostic::UncoveredCases {
+                node: self.node_name(index),
+                variables: variables
+                    .iter()
+                    .map(|x| self.variable_name(*x).into())
+                    .collect(),
+                combinations: uncovered,
+            });
+        }
+    }
+
+    fn node_name(&self, index: usize) -> Arc<str> {
+        self.node_names
+            .get(index)
+            .cloned()
+            .unwrap_or_else(|| format!("#{index}").into())
+    }
+}
+
+// Frames can only be shown for a positive and finite number of seconds.
+fn is_duration(seconds: f32) -> bool {
+    seconds.is_finite() && seconds > 0.0
+}
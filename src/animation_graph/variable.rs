@@ -1,14 +1,82 @@
-#[derive(Debug, Clone)]
+use std::{cmp::Ordering, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Variable {
     Bool(bool),
     Enum(String),
+    Int(i64),
+    Float(f32),
+    Trigger(bool), // a bool that is reset by the graph once a transition uses it
     Any,
+    Compare(Comparison, Box<Variable>), // matches numbers that compare to the value, only valid in cases
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Comparison {
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    pub const OPERATORS: [(&'static str, Comparison); 5] = [
+        ("!=", Comparison::NotEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    pub fn operator(&self) -> &'static str {
+        Self::OPERATORS
+            .iter()
+            .find(|(_, comparison)| comparison == self)
+            .map(|(operator, _)| *operator)
+            .unwrap_or_default()
+    }
+
+    fn accepts(&self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::NotEqual => ordering != Ordering::Equal,
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::LessOrEqual => ordering != Ordering::Greater,
+            Comparison::Greater => ordering == Ordering::Greater,
+            Comparison::GreaterOrEqual => ordering != Ordering::Less,
+        }
+    }
 }
 
 impl Variable {
     pub fn is_any(&self) -> bool {
         matches!(self, Variable::Any)
     }
+
+    /// Patterns can only be used in cases, a variable can never hold one.
+    pub fn is_pattern(&self) -> bool {
+        matches!(self, Variable::Any | Variable::Compare(..))
+    }
+
+    /// Checks a case value against the current value of a variable.
+    pub fn matches(&self, value: &Variable) -> bool {
+        match (self, value) {
+            (Variable::Compare(comparison, expected), _) => value
+                .compare(expected)
+                .is_some_and(|ordering| comparison.accepts(ordering)),
+            _ => self == value,
+        }
+    }
+
+    fn compare(&self, other: &Variable) -> Option<Ordering> {
+        match (self, other) {
+            (Variable::Int(l0), Variable::Int(r0)) => l0.partial_cmp(r0),
+            (Variable::Float(l0), Variable::Float(r0)) => l0.partial_cmp(r0),
+            _ => None,
+        }
+    }
 }
 
 impl PartialEq for Variable {
@@ -16,9 +84,64 @@ impl PartialEq for Variable {
         match (self, other) {
             (Self::Bool(l0), Self::Bool(r0)) => l0 == r0,
             (Self::Enum(l0), Self::Enum(r0)) => l0 == r0,
+            (Self::Int(l0), Self::Int(r0)) => l0 == r0,
+            (Self::Float(l0), Self::Float(r0)) => l0 == r0,
+            (Self::Trigger(l0), Self::Trigger(r0)) => l0 == r0,
+            (Self::Compare(l0, l1), Self::Compare(r0, r1)) => l0 == r0 && l1 == r1,
             (Variable::Any, _) => true,
             (_, Variable::Any) => true,
             _ => core::mem::discriminant(self) == core::mem::discriminant(other),
         }
     }
 }
+
+impl Display for Variable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Variable::Bool(x) => write!(f, "{x}"),
+            Variable::Enum(x) => write!(f, "{x}"),
+            Variable::Int(x) => write!(f, "{x}"),
+            Variable::Float(x) => write!(f, "{x:?}"),
+            Variable::Trigger(x) => write!(f, "{x}"),
+            Variable::Any => write!(f, "*"),
+            Variable::Compare(comparison, x) => write!(f, "{}{x}", comparison.operator()),
+        }
+    }
+}
+
+/// The values a variable is allowed to take.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableDomain {
+    Bool,
+    Enum(Vec<String>),
+    Int,
+    Float,
+    Trigger,
+}
+
+impl VariableDomain {
+    pub fn contains(&self, value: &Variable) -> bool {
+        match (self, value) {
+            (VariableDomain::Bool, Variable::Bool(_)) => true,
+            (VariableDomain::Enum(values), Variable::Enum(x)) => values.contains(x),
+            (VariableDomain::Int, Variable::Int(_)) => true,
+            (VariableDomain::Float, Variable::Float(x)) => x.is_finite(),
+            (VariableDomain::Trigger, Variable::Trigger(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Every value of the domain, `None` for numbers since there are too many to list.
+    pub fn values(&self) -> Option<Vec<Variable>> {
+        match self {
+            VariableDomain::Bool => Some(vec![Variable::Bool(false), Variable::Bool(true)]),
+            VariableDomain::Trigger => {
+                Some(vec![Variable::Trigger(false), Variable::Trigger(true)])
+            }
+            VariableDomain::Enum(values) => {
+                Some(values.iter().cloned().map(Variable::Enum).collect())
+            }
+            VariableDomain::Int | VariableDomain::Float => None,
+        }
+    }
+}
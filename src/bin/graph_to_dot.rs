@@ -0,0 +1,33 @@
+//! Turns an animation graph into a Graphviz DOT file.
+//!
+//! Usage: graph_to_dot [graph.anim.json] [output.dot]
+//! The graph defaults to assets/graph.anim.json and the DOT file is written to stdout without an output path.
+
+use std::{fs, process::ExitCode};
+
+use bevy_tests::prelude::*;
+
+const DEFAULT_GRAPH: &str = "assets/graph.anim.json";
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let input = args.next().unwrap_or_else(|| DEFAULT_GRAPH.to_string());
+    let output = args.next();
+
+    match export(&input, output.as_deref()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("could not export `{input}`: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn export(input: &str, output: Option<&str>) -> Result<()> {
+    let dot = AnimationGraphDefinition::from_file(input)?.to_dot();
+    match output {
+        Some(path) => fs::write(path, dot)?,
+        None => print!("{dot}"),
+    }
+    Ok(())
+}
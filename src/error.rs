@@ -39,3 +39,13 @@ impl From<ParseIntError> for GameError {
         GameError(value.to_string())
     }
 }
+impl From<bevy::asset::ReadAssetBytesError> for GameError {
+    fn from(value: bevy::asset::ReadAssetBytesError) -> Self {
+        GameError(value.to_string())
+    }
+}
+impl From<serde_json::Error> for GameError {
+    fn from(value: serde_json::Error) -> Self {
+        GameError(value.to_string())
+    }
+}
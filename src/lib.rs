@@ -0,0 +1,5 @@
+pub mod animation_graph;
+pub mod camera;
+pub mod error;
+pub mod prelude;
+pub mod world;
@@ -1,14 +1,13 @@
-use crate::prelude::*;
-
 use bevy::{prelude::*, render::camera::ScalingMode};
-use camera::{CameraSettings, EditorCamera, GameCameraPlugin};
-use world::WorldPlugin;
-
-mod animation_graph;
-mod camera;
-mod error;
-mod prelude;
-mod world;
+use bevy_tests::{
+    animation_graph::{
+        AnimationGraphDebugPlugin, AnimationGraphDebugTarget, AnimationGraphHandle,
+        AnimationGraphPlugin,
+    },
+    camera::{CameraSettings, EditorCamera, GameCameraPlugin},
+    prelude::*,
+    world::WorldPlugin,
+};
 
 #[derive(Component)]
 pub struct GamePlayer;
@@ -27,6 +26,8 @@ fn main() {
                     ..Default::default()
                 }),
         )
+        .add_plugins(AnimationGraphPlugin)
+        .add_plugins(AnimationGraphDebugPlugin)
         .add_plugins(WorldPlugin)
         .add_plugins(GameCameraPlugin)
         .add_systems(Startup, create_player)
@@ -37,16 +38,19 @@ fn main() {
 
 pub fn update_player(
     keys: Res<ButtonInput<KeyCode>>,
-    query: Single<(&GamePlayer, &mut CharacterAnimationGraph)>,
+    query: Single<(&GamePlayer, Option<&mut CharacterAnimationGraph>)>,
     camera_query: Single<&Camera, With<EditorCamera>>,
 ) {
     if camera_query.into_inner().is_active {
         return;
     }
-    let (_, mut graph) = query.into_inner();
+    // The graph is only inserted once its asset has loaded.
+    let (_, Some(mut graph)) = query.into_inner() else {
+        return;
+    };
     if keys.just_pressed(KeyCode::Space) {
         info!("SPACE");
-        graph.set_variable("attacking", Variable::Bool(true));
+        graph.set_variable("attacking", Variable::Trigger(true));
     }
 }
 
@@ -56,8 +60,7 @@ pub fn move_player(
     query: Single<(
         &GamePlayer,
         &mut Transform,
-        &mut Sprite,
-        &mut CharacterAnimationGraph,
+        Option<&mut CharacterAnimationGraph>,
     )>,
     camera_query: Single<&Camera, With<EditorCamera>>,
 ) {
@@ -65,7 +68,7 @@ pub fn move_player(
         return;
     }
 
-    let (_, mut player_transform, mut sprite, mut graph) = query.into_inner();
+    let (_, mut player_transform, mut graph) = query.into_inner();
 
     let delta = time.delta_secs();
 
@@ -74,37 +77,36 @@ pub fn move_player(
     let mut movement_vector = Vec3::ZERO;
 
     if keys.pressed(KeyCode::KeyW) {
-        graph.set_variable("directionY", Variable::Enum("up".to_string()));
-
         movement_vector.y += 1.0;
     }
     if keys.pressed(KeyCode::KeyS) {
-        graph.set_variable("directionY", Variable::Enum("down".to_string()));
         movement_vector.y -= 1.0;
     }
 
     if keys.pressed(KeyCode::KeyA) {
-        graph.set_variable("directionX", Variable::Enum("left".to_string()));
         movement_vector.x += 1.0;
     } else if keys.pressed(KeyCode::KeyD) {
-        graph.set_variable("directionX", Variable::Enum("right".to_string()));
         movement_vector.x -= 1.0;
     }
 
-    if (keys.pressed(KeyCode::KeyW) || keys.pressed(KeyCode::KeyS))
-        && !keys.pressed(KeyCode::KeyD)
-        && !keys.pressed(KeyCode::KeyA)
-    {
-        graph.set_variable("directionX", Variable::Enum("none".to_string()));
-    }
-
-    graph.set_variable("walking", Variable::Bool(movement_vector != Vec3::ZERO));
-
-    if let Some(next_frame) = graph.get_next_index(delta) {
-        sprite.texture_atlas.as_mut().unwrap().index = next_frame;
-        if let Some(animation) = graph.get_current_animation() {
-            sprite.flip_x = animation.flip_x;
+    // Sideways wins over up and down, the last direction is kept while standing still.
+    let facing = if keys.pressed(KeyCode::KeyA) {
+        Some("left")
+    } else if keys.pressed(KeyCode::KeyD) {
+        Some("right")
+    } else if keys.pressed(KeyCode::KeyS) {
+        Some("down")
+    } else if keys.pressed(KeyCode::KeyW) {
+        Some("up")
+    } else {
+        None
+    };
+    // Movement does not wait for the animation graph to load.
+    if let Some(graph) = graph.as_mut() {
+        if let Some(facing) = facing {
+            graph.set_variable("facing", Variable::Enum(facing.to_string()));
         }
+        graph.set_variable("walking", Variable::Bool(movement_vector != Vec3::ZERO));
     }
 
     if movement_vector == Vec3::ZERO {
@@ -137,7 +139,8 @@ pub fn create_player(
                     },
                 )
             },
-            CharacterAnimationGraph::new(),
+            AnimationGraphHandle(asset_server.load("graph.anim.json")),
+            AnimationGraphDebugTarget,
             Transform::from_xyz(0., 0., 2.),
         ))
         .with_child((
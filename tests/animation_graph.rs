@@ -0,0 +1,1434 @@
+use std::sync::Arc;
+
+use bevy::math::UVec2;
+use bevy_tests::{
+    animation_graph::{
+        aseprite::AsepriteSheet,
+        harness::{AnimationSample, AnimationScript},
+        snapshot::AnimationGraphSnapshot,
+        AnimationLayer, GraphReader, MAX_SPEED,
+    },
+    prelude::*,
+};
+
+const DELTA: f32 = 0.05;
+
+fn player_graph() -> CharacterAnimationGraph {
+    CharacterAnimationGraph::from_file("assets/graph.anim.json").unwrap()
+}
+
+fn definition(json: &str) -> Arc<AnimationGraphDefinition> {
+    Arc::new(
+        GraphReader::from_json(json)
+            .unwrap()
+            .into_definition()
+            .unwrap(),
+    )
+}
+
+fn graph(json: &str) -> CharacterAnimationGraph {
+    CharacterAnimationGraph::new(definition(json))
+}
+
+fn load_error(json: &str) -> String {
+    GraphReader::from_json(json)
+        .unwrap()
+        .into_definition()
+        .unwrap_err()
+        .to_string()
+}
+
+// The logged transitions of `graph`, formatted as `from -> to (cause)`.
+fn transitions_of(graph: &CharacterAnimationGraph) -> Vec<String> {
+    graph
+        .transitions()
+        .map(|x| format!("{} -> {} ({})", x.from, x.to, x.cause))
+        .collect()
+}
+
+fn direction(value: &str) -> Variable {
+    Variable::Enum(value.to_string())
+}
+
+fn walk_right() -> AnimationScript {
+    AnimationScript::new()
+        .set("walking", Variable::Bool(true))
+        .set("facing", direction("right"))
+}
+
+fn frames_of<'a>(samples: &'a [AnimationSample], node: &str) -> Vec<&'a AnimationSample> {
+    samples.iter().filter(|x| x.node.as_ref() == node).collect()
+}
+
+#[test]
+fn standing_loops_the_front_idle_animation() {
+    let samples = AnimationScript::new()
+        .ticks(50, DELTA)
+        .run(&mut player_graph())
+        .unwrap();
+
+    assert!(samples.len() > 6);
+    for (index, sample) in samples.iter().enumerate() {
+        assert_eq!(sample.node.as_ref(), "idle");
+        assert_eq!(sample.frame, index % 6);
+        assert!(!sample.flip_x);
+    }
+}
+
+#[test]
+fn attack_while_walking_right_plays_flipped_frames_then_walks() {
+    let samples = walk_right()
+        .ticks(10, DELTA)
+        .set("attacking", Variable::Trigger(true))
+        .ticks(20, DELTA)
+        .run(&mut player_graph())
+        .unwrap();
+
+    let start = samples
+        .iter()
+        .position(|x| x.node.as_ref() == "attack")
+        .unwrap();
+    let attack = &samples[start..start + 4];
+    assert_eq!(
+        attack.iter().map(|x| x.frame).collect::<Vec<_>>(),
+        [42, 43, 44, 45]
+    );
+    assert!(attack
+        .iter()
+        .all(|x| x.node.as_ref() == "attack" && x.flip_x));
+
+    let after = &samples[start + 4];
+    assert_eq!(after.node.as_ref(), "walk");
+    assert_eq!(after.frame, 24);
+    assert!(after.flip_x);
+}
+
+#[test]
+fn attack_is_not_interrupted_by_turning() {
+    let samples = walk_right()
+        .tick(DELTA)
+        .set("attacking", Variable::Trigger(true))
+        .ticks(2, DELTA)
+        .set("facing", direction("left"))
+        .ticks(20, DELTA)
+        .run(&mut player_graph())
+        .unwrap();
+
+    let attack = frames_of(&samples, "attack");
+    assert_eq!(
+        attack.iter().map(|x| x.frame).collect::<Vec<_>>(),
+        [42, 43, 44, 45]
+    );
+    assert!(attack.iter().all(|x| x.flip_x));
+
+    let walking = samples
+        .iter()
+        .filter(|x| x.node.as_ref() == "walk" && x.time > attack[3].time)
+        .collect::<Vec<_>>();
+    assert!(!walking.is_empty());
+    assert!(walking.iter().all(|x| x.frame >= 24 && !x.flip_x));
+}
+
+#[test]
+fn facing_picks_the_directional_variant() {
+    let cases = [
+        ("down", 18, false),
+        ("left", 24, false),
+        ("right", 24, true),
+        ("up", 30, false),
+    ];
+    for (facing, frame, flip_x) in cases {
+        let samples = AnimationScript::new()
+            .set("walking", Variable::Bool(true))
+            .set("facing", direction(facing))
+            .tick(DELTA)
+            .run(&mut player_graph())
+            .unwrap();
+        assert_eq!((samples[0].frame, samples[0].flip_x), (frame, flip_x));
+    }
+}
+
+#[test]
+fn frames_are_shown_for_their_duration() {
+    let samples = walk_right()
+        .ticks(2000, 0.001)
+        .run(&mut player_graph())
+        .unwrap();
+
+    for pair in samples.windows(2) {
+        let shown = pair[1].time - pair[0].time;
+        assert!((shown - 1.0 / 6.0).abs() < 0.002, "frame shown for {shown}");
+    }
+}
+
+#[test]
+fn same_script_shows_same_frames() {
+    let script = walk_right()
+        .ticks(7, 0.031)
+        .set("attacking", Variable::Trigger(true))
+        .ticks(9, 0.047)
+        .set("walking", Variable::Bool(false))
+        .ticks(30, 0.023);
+
+    let first = script.run(&mut player_graph()).unwrap();
+    let second = script.run(&mut player_graph()).unwrap();
+    assert_eq!(first, second);
+}
+
+#[test]
+fn restored_snapshot_continues_from_the_same_frame() {
+    let mut graph = player_graph();
+    walk_right()
+        .ticks(9, 0.037)
+        .set("attacking", Variable::Trigger(true))
+        .ticks(3, 0.037)
+        .run(&mut graph)
+        .unwrap();
+
+    let mut restored = player_graph();
+    restored.restore(&graph.snapshot()).unwrap();
+
+    let script = AnimationScript::new().ticks(40, 0.023);
+    assert_eq!(
+        script.run(&mut graph).unwrap(),
+        script.run(&mut restored).unwrap()
+    );
+}
+
+#[test]
+fn attack_can_be_queried_while_it_plays() {
+    let mut graph = player_graph();
+    walk_right()
+        .tick(DELTA)
+        .set("attacking", Variable::Trigger(true))
+        .tick(DELTA)
+        .run(&mut graph)
+        .unwrap();
+
+    assert_eq!(graph.current_state(), Some("attack"));
+    assert!(graph.is_locking());
+    assert_eq!(graph.variable("attacking"), Some(&Variable::Trigger(false)));
+    assert_eq!(graph.variable("facing"), Some(&direction("right")));
+    assert_eq!(graph.variable("unknown"), None);
+
+    let mut remaining = graph.frames_remaining().unwrap();
+    let mut progress = graph.progress().unwrap();
+    assert_eq!(remaining, 3);
+    while graph.current_state() == Some("attack") && remaining > 0 {
+        AnimationScript::new().tick(DELTA).run(&mut graph).unwrap();
+        assert!(graph.progress().unwrap() >= progress);
+        assert!(graph.frames_remaining().unwrap() <= remaining);
+        progress = graph.progress().unwrap();
+        remaining = graph.frames_remaining().unwrap();
+    }
+    assert_eq!(remaining, 0);
+    assert!(progress >= 0.75);
+}
+
+#[test]
+fn change_with_priority_interrupts_attack() {
+    let mut graph = player_graph();
+    let samples = walk_right()
+        .tick(DELTA)
+        .set("attacking", Variable::Trigger(true))
+        .ticks(2, DELTA)
+        .set_with_priority("facing", direction("left"), 1)
+        .ticks(2, DELTA)
+        .run(&mut graph)
+        .unwrap();
+
+    let attack = frames_of(&samples, "attack");
+    assert!(attack.len() < 4);
+    let last = samples.last().unwrap();
+    assert_eq!(last.node.as_ref(), "walk");
+    assert!(!last.flip_x);
+    assert_eq!(graph.pending_priority(), None);
+}
+
+#[test]
+fn change_without_priority_waits_for_attack() {
+    let mut graph = player_graph();
+    walk_right()
+        .tick(DELTA)
+        .set("attacking", Variable::Trigger(true))
+        .tick(DELTA)
+        .set("facing", direction("left"))
+        .tick(DELTA)
+        .run(&mut graph)
+        .unwrap();
+
+    assert_eq!(graph.current_state(), Some("attack"));
+    assert_eq!(graph.priority(), 1);
+    assert_eq!(graph.pending_priority(), Some(0));
+}
+
+#[test]
+fn turning_while_walking_keeps_the_step() {
+    let samples = walk_right()
+        .ticks(7, DELTA)
+        .set("facing", direction("left"))
+        .ticks(4, DELTA)
+        .run(&mut player_graph())
+        .unwrap();
+
+    let turned = samples.iter().position(|x| !x.flip_x).unwrap();
+    assert_eq!(samples[turned].frame, samples[turned - 1].frame);
+    assert_eq!(samples[turned + 1].frame, samples[turned].frame + 1);
+    assert!(samples[turned + 1].time - samples[turned].time < 1.0 / 6.0);
+}
+
+#[test]
+fn transition_clip_plays_before_the_state() {
+    let mut graph = GraphReader::from_json(
+        r#"{
+            "animations": [
+                { "idle": { "start": 0, "count": 2, "animation_duration": 0.2 } },
+                { "startWalking": { "start": 4, "count": 2, "animation_duration": 0.2 } },
+                { "walk": { "start": 8, "count": 2, "animation_duration": 0.2 } }
+            ],
+            "variables": { "walking": false },
+            "root": "decide",
+            "conditions": [{
+                "name": "decide", "variables": ["walking"], "values": [true],
+                "success": "walk", "failure": "idle"
+            }],
+            "states": [
+                { "current": "idle", "next": "loop" },
+                { "current": "walk", "next": "loop", "transition": "startWalking" }
+            ]
+        }"#,
+    )
+    .unwrap()
+    .into_graph()
+    .unwrap();
+
+    let samples = AnimationScript::new()
+        .ticks(10, 0.03)
+        .set("walking", Variable::Bool(true))
+        .ticks(30, 0.03)
+        .run(&mut graph)
+        .unwrap();
+
+    let mut frames = samples.iter().map(|x| x.frame).collect::<Vec<_>>();
+    frames.dedup();
+    assert_eq!(frames[..7], [0, 4, 5, 8, 9, 8, 9]);
+    assert_eq!(frames_of(&samples, "walk").len(), samples.len() - 1);
+}
+
+#[test]
+fn graphs_share_their_definition_but_not_their_state() {
+    let definition =
+        Arc::new(AnimationGraphDefinition::from_file("assets/graph.anim.json").unwrap());
+    let mut walker = CharacterAnimationGraph::new(definition.clone());
+    let mut stander = CharacterAnimationGraph::new(definition.clone());
+
+    let walking = walk_right().ticks(5, DELTA).run(&mut walker).unwrap();
+    let standing = AnimationScript::new()
+        .ticks(5, DELTA)
+        .run(&mut stander)
+        .unwrap();
+
+    assert!(walking.iter().all(|x| x.node.as_ref() == "walk"));
+    assert!(standing.iter().all(|x| x.node.as_ref() == "idle"));
+    assert_eq!(stander.variable("walking"), Some(&Variable::Bool(false)));
+    assert!(Arc::ptr_eq(walker.definition(), stander.definition()));
+    assert_eq!(Arc::strong_count(&definition), 3);
+}
+
+const SHEET: &str = r#"{
+    "frames": [
+        { "frame": { "x": 0, "y": 0, "w": 32, "h": 32 }, "duration": 100 },
+        { "frame": { "x": 32, "y": 0, "w": 32, "h": 32 }, "duration": 100 },
+        { "frame": { "x": 0, "y": 32, "w": 32, "h": 32 }, "duration": 50 },
+        { "frame": { "x": 32, "y": 32, "w": 32, "h": 32 }, "duration": 150 }
+    ],
+    "meta": {
+        "size": { "w": 64, "h": 64 },
+        "frameTags": [
+            { "name": "idle", "from": 0, "to": 1, "direction": "forward" },
+            { "name": "attackLeft", "from": 2, "to": 3, "direction": "forward", "repeat": "1" }
+        ]
+    }
+}"#;
+
+#[test]
+fn aseprite_tags_become_animations() {
+    let sheet = AsepriteSheet::from_slice(SHEET.as_bytes()).unwrap();
+    assert_eq!(sheet.layout.textures.len(), 4);
+    assert_eq!(sheet.layout.size, UVec2::new(64, 64));
+
+    let attack = &sheet.animations["attackLeft"];
+    assert_eq!((attack.start, attack.count), (2, 2));
+    assert_eq!(attack.frame_durations, Some(vec![0.05, 0.15]));
+    assert_eq!(attack.playback, Playback::Once);
+    assert_eq!(sheet.animations["idle"].playback, Playback::Loop);
+
+    let mut reader = GraphReader::from_json(
+        r#"{
+            "animations": [{ "attackRight": { "tag": "attackLeft", "flip_x": true } }],
+            "variables": { "attacking": "trigger" },
+            "root": "decide",
+            "conditions": [{
+                "name": "decide", "variables": ["attacking"], "values": [true],
+                "success": "attackRight", "failure": "idle"
+            }],
+            "states": [
+                { "current": "idle", "next": "loop" },
+                { "current": "attackRight", "next": "loop", "locking": true }
+            ]
+        }"#,
+    )
+    .unwrap();
+    reader.import_sheet(&sheet);
+    let mut graph = reader.into_graph().unwrap();
+
+    let samples = AnimationScript::new()
+        .set("attacking", Variable::Trigger(true))
+        .ticks(8, DELTA)
+        .run(&mut graph)
+        .unwrap();
+    let frames = samples
+        .iter()
+        .map(|x| (x.node.as_ref(), x.frame, x.flip_x))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        frames[..3],
+        [
+            ("attackRight", 2, true),
+            ("attackRight", 3, true),
+            ("idle", 0, false)
+        ]
+    );
+}
+
+fn looping_clip(count: usize) -> String {
+    format!(
+        r#"{{
+            "animations": [{{ "idle": {{ "start": 10, "count": {count}, "animation_duration": {count}.0, "playback": "loop" }} }}],
+            "root": "idle",
+            "states": [{{ "current": "idle", "next": "loop" }}]
+        }}"#
+    )
+}
+
+#[test]
+fn reload_drops_frames_past_the_end_of_a_shortened_clip() {
+    let mut graph = graph(&looping_clip(6));
+    AnimationScript::new()
+        .ticks(9, 0.5)
+        .run(&mut graph)
+        .unwrap();
+
+    graph.reload(&definition(&looping_clip(2)));
+    let samples = AnimationScript::new()
+        .ticks(20, 0.5)
+        .run(&mut graph)
+        .unwrap();
+
+    assert!(!samples.is_empty());
+    assert!(samples.iter().all(|x| (10..12).contains(&x.frame)));
+    assert_eq!(graph.frames_remaining(), None);
+    assert!(graph.progress().unwrap() <= 1.0);
+}
+
+#[test]
+fn switches_on_numbers_need_a_default_or_a_wildcard() {
+    let switch = |variables: &str, cases: &str, default: &str| {
+        format!(
+            r#"{{
+                "animations": [{{ "idle": {{ "start": 0, "count": 2, "animation_duration": 0.2, "playback": "loop" }} }}],
+                "variables": {{ "hp": 0, "dir": "a, b" }},
+                "root": "decide",
+                "switches": [{{
+                    "name": "decide", "variables": {variables}, "cases": {cases},
+                    "results": ["idle", "idle"]{default}
+                }}],
+                "states": [{{ "current": "idle", "next": "loop" }}]
+            }}"#
+        )
+    };
+
+    let error = load_error(&switch(r#"["hp"]"#, "[[0]]", ""));
+    assert!(
+        error.contains("`decide` has no case for: hp = *"),
+        "{error}"
+    );
+
+    let error = load_error(&switch(r#"["hp", "dir"]"#, r#"[["*", "a"], [3, "b"]]"#, ""));
+    assert!(
+        error.contains("`decide` has no case for: hp = *, dir = b"),
+        "{error}"
+    );
+
+    graph(&switch(
+        r#"["hp", "dir"]"#,
+        r#"[["*", "a"], ["*", "b"]]"#,
+        "",
+    ));
+    graph(&switch(
+        r#"["hp", "dir"]"#,
+        r#"[[0, "a"], [3, "b"]]"#,
+        r#", "default": "idle""#,
+    ));
+}
+
+#[test]
+fn validation_reports_every_problem_of_a_graph() {
+    let error = load_error(
+        r#"{
+            "animations": [{ "idle": { "start": 0, "count": 2, "animation_duration": 0.2 } }],
+            "variables": { "walking": false },
+            "root": "first",
+            "setters": [
+                { "name": "first", "variables": ["walking"], "values": [true], "next": "second" },
+                { "name": "second", "variables": ["walking"], "values": [false], "next": "first" }
+            ],
+            "states": [
+                { "current": "idle", "next": "loop" },
+                { "current": "walk", "next": "loop" }
+            ]
+        }"#,
+    );
+
+    assert!(error.starts_with("invalid animation graph:"));
+    assert!(error.contains("`walk` plays `walk` which is not an animation"));
+    assert!(error.contains("`idle` can not be reached from the root"));
+    assert!(error.contains("nodes loop without reaching a state: first -> second -> first"));
+}
+
+#[test]
+fn valid_graph_passes_validation() {
+    let definition = AnimationGraphDefinition::from_file("assets/graph.anim.json").unwrap();
+    assert_eq!(definition.validate(), Ok(()));
+}
+
+const FACING_GRAPH: &str = r#"{
+    "animations": [
+        { "front": { "start": 0, "count": 2, "animation_duration": 0.2, "playback": "loop" } },
+        { "side": { "start": 2, "count": 2, "animation_duration": 0.2, "playback": "loop" } }
+    ],
+    "variables": { "facing": "down, left, right", "walking": false },
+    "root": "decide",
+    "switches": [{
+        "name": "decide", "variables": ["facing", "walking"],
+        "cases": [["down", "*"], ["left", "*"], ["right", "*"]],
+        "results": ["front", "side", "side"]
+    }],
+    "states": [
+        { "current": "front", "next": "loop" },
+        { "current": "side", "next": "loop" }
+    ]
+}"#;
+
+#[test]
+fn switch_transitions_log_the_matched_case() {
+    let mut graph = graph(
+        &FACING_GRAPH
+            .replace(r#", ["right", "*"]]"#, "]")
+            .replace(r#", "side"]"#, r#"], "default": "side""#),
+    );
+    graph.log_transitions(64);
+    AnimationScript::new()
+        .tick(0.2)
+        .set("facing", direction("right"))
+        .tick(DELTA)
+        .run(&mut graph)
+        .unwrap();
+
+    assert_eq!(
+        transitions_of(&graph),
+        [
+            "loop -> decide (root)",
+            "decide -> front (case facing = down, walking = *)",
+            "front -> loop (changed facing)",
+            "loop -> decide (root)",
+            "decide -> side (default)",
+        ]
+    );
+}
+
+#[test]
+fn switches_without_default_cover_every_value() {
+    graph(FACING_GRAPH);
+
+    let error = load_error(&FACING_GRAPH.replace(r#"["right", "*"]"#, r#"["right", true]"#));
+    assert!(error.contains("`decide` has no case for: facing = right, walking = false"));
+
+    let error = load_error(&FACING_GRAPH.replace(r#"["left", "*"]"#, r#"["sideways", "*"]"#));
+    assert!(error.contains("`decide` uses `sideways` which is not a value of `facing`"));
+    assert!(error.contains("facing = left, walking = false; facing = left, walking = true"));
+}
+
+#[test]
+fn values_outside_the_domain_are_not_set() {
+    let mut graph = graph(FACING_GRAPH);
+    assert_eq!(graph.variable("facing"), Some(&direction("down")));
+
+    graph.set_variable("facing", direction("up"));
+    graph.set_variable("walking", Variable::Int(1));
+    graph.set_variable("facing", Variable::Any);
+    assert_eq!(graph.variable("facing"), Some(&direction("down")));
+    assert_eq!(graph.variable("walking"), Some(&Variable::Bool(false)));
+
+    graph.set_variable("facing", direction("left"));
+    assert_eq!(graph.variable("facing"), Some(&direction("left")));
+}
+
+const SPEED_GRAPH: &str = r#"{
+    "animations": [
+        { "idle": { "start": 0, "count": 1, "animation_duration": 0.1, "playback": "loop" } },
+        { "walk": { "start": 1, "count": 1, "animation_duration": 0.1, "playback": "loop" } },
+        { "run": { "start": 2, "count": 1, "animation_duration": 0.1, "playback": "loop" } },
+        { "limp": { "start": 3, "count": 1, "animation_duration": 0.1, "playback": "loop" } }
+    ],
+    "variables": { "speed": 0.0, "health": 100 },
+    "root": "hurt",
+    "conditions": [{
+        "name": "hurt", "variables": ["health"], "values": ["< 30"],
+        "success": "limp", "failure": "move"
+    }],
+    "switches": [{
+        "name": "move", "variables": ["speed"],
+        "cases": [[">= 50.0"], ["!= 0.0"]],
+        "results": ["run", "walk"],
+        "default": "idle"
+    }],
+    "states": [
+        { "current": "idle", "next": "loop" },
+        { "current": "walk", "next": "loop" },
+        { "current": "run", "next": "loop" },
+        { "current": "limp", "next": "loop" }
+    ]
+}"#;
+
+#[test]
+fn numbers_pick_cases_by_comparison() {
+    let mut graph = graph(SPEED_GRAPH);
+    let cases = [
+        ("speed", Variable::Float(0.0), "idle"),
+        ("speed", Variable::Float(12.5), "walk"),
+        ("speed", Variable::Float(50.0), "run"),
+        ("health", Variable::Int(30), "run"),
+        ("health", Variable::Int(29), "limp"),
+    ];
+    for (name, value, state) in cases {
+        graph.set_variable(name, value);
+        AnimationScript::new().tick(0.25).run(&mut graph).unwrap();
+        assert_eq!(graph.current_state(), Some(state));
+    }
+}
+
+#[test]
+fn numbers_are_checked_when_loading() {
+    let error = load_error(&SPEED_GRAPH.replace(r#"">= 50.0""#, r#""fast""#));
+    assert!(error.contains("`move` compares `speed` with `fast` which is not a valid comparison"));
+
+    let error = load_error(&SPEED_GRAPH.replace(r#""< 30""#, "29.5"));
+    assert!(error.contains("`hurt` assigns 29.5 to `health`"));
+}
+
+const EVENT_GRAPH: &str = r#"{
+    "animations": [{
+        "swing": {
+            "start": 8, "count": 4, "animation_duration": 0.4,
+            "events": [{ "frame": 0, "name": "whoosh" }, { "frame": 2, "name": "hit" }]
+        }
+    }],
+    "root": "swing",
+    "states": [{ "current": "swing", "next": "loop" }]
+}"#;
+
+#[test]
+fn events_fire_when_their_frame_is_shown() {
+    let mut graph = graph(EVENT_GRAPH);
+
+    let mut fired = Vec::new();
+    for _ in 0..18 {
+        if let Some(frame) = graph.get_next_index(DELTA).unwrap() {
+            for (animation, event) in graph.drain_events() {
+                fired.push((frame.index, animation.to_string(), event.to_string()));
+            }
+        }
+    }
+
+    let swing = |frame: usize, event: &str| (frame, "swing".to_string(), event.to_string());
+    assert_eq!(
+        fired,
+        [
+            swing(8, "whoosh"),
+            swing(10, "hit"),
+            swing(8, "whoosh"),
+            swing(10, "hit"),
+        ]
+    );
+    assert_eq!(graph.drain_events().count(), 0);
+}
+
+#[test]
+fn events_past_the_last_frame_are_rejected() {
+    let error = load_error(&EVENT_GRAPH.replace(r#""frame": 2"#, r#""frame": 4"#));
+    assert!(error.contains("`swing` fires `hit` on frame 4 but only has 4 frames"));
+}
+
+const TURN_GRAPH: &str = r#"{
+    "animations": [
+        { "turn": { "start": 0, "count": 2, "animation_duration": 0.2 } },
+        { "walkRight": { "start": 4, "count": 2, "animation_duration": 0.2, "flip_x": true, "playback": "loop" } }
+    ],
+    "root": "turn",
+    "states": [
+        { "current": "turn", "next": "walkRight" },
+        { "current": "walkRight", "next": "loop" }
+    ]
+}"#;
+
+#[test]
+fn last_frame_of_a_clip_keeps_its_own_flip() {
+    let mut graph = graph(TURN_GRAPH);
+
+    let mut frames = Vec::new();
+    for _ in 0..12 {
+        if let Some(frame) = graph.get_next_index(DELTA).unwrap() {
+            frames.push((frame.index, frame.flip_x));
+        }
+    }
+    assert_eq!(frames[..4], [(0, false), (1, false), (4, true), (5, true)]);
+}
+
+#[test]
+fn clips_without_frames_or_time_are_rejected() {
+    let clip = |fields: &str| {
+        load_error(&format!(
+            r#"{{
+                "animations": [{{ "idle": {{ "start": 0, {fields} }} }}],
+                "root": "idle",
+                "states": [{{ "current": "idle", "next": "loop" }}]
+            }}"#
+        ))
+    };
+
+    assert!(clip(r#""count": 0, "animation_duration": 1.0"#).contains("`idle` has no frames"));
+    assert!(clip(r#""count": 2, "animation_duration": 0.0"#)
+        .contains("`idle` lasts 0 seconds which is not a positive duration"));
+    assert!(clip(r#""count": 2, "animation_duration": -1.0"#)
+        .contains("`idle` lasts -1 seconds which is not a positive duration"));
+    assert!(clip(r#""count": 2, "frame_durations": [0.1, 0.0]"#)
+        .contains("`idle` has 2 frames but the frame durations are [0.1, 0.0]"));
+    assert!(clip(r#""count": 2, "frame_durations": [0.1]"#)
+        .contains("`idle` has 2 frames but the frame durations are [0.1]"));
+}
+
+#[test]
+fn playback_modes_order_the_frames() {
+    let cases = [
+        ("once", [0, 1, 2, 10, 10, 10]),
+        ("loop", [0, 1, 2, 0, 1, 2]),
+        ("hold", [0, 1, 2, 2, 2, 2]),
+        ("pingPong", [0, 1, 2, 1, 0, 1]),
+        ("reverse", [2, 1, 0, 10, 10, 10]),
+        ("reverseLoop", [2, 1, 0, 2, 1, 0]),
+        ("pingPongReverse", [2, 1, 0, 1, 2, 1]),
+    ];
+    for (playback, expected) in cases {
+        let mut graph = graph(&format!(
+            r#"{{
+                "animations": [
+                    {{ "clip": {{ "start": 0, "count": 3, "animation_duration": 0.3, "playback": "{playback}" }} }},
+                    {{ "after": {{ "start": 10, "count": 1, "animation_duration": 0.1, "playback": "loop" }} }}
+                ],
+                "root": "clip",
+                "states": [
+                    {{ "current": "clip", "next": "after" }},
+                    {{ "current": "after", "next": "loop" }}
+                ]
+            }}"#
+        ));
+
+        let samples = AnimationScript::new()
+            .ticks(20, DELTA)
+            .run(&mut graph)
+            .unwrap();
+        let frames = samples.iter().map(|x| x.frame).collect::<Vec<_>>();
+        assert_eq!(frames[..6], expected, "{playback}");
+        let finished = matches!(playback, "once" | "reverse");
+        let state = if finished { "after" } else { "clip" };
+        assert_eq!(graph.current_state(), Some(state), "{playback}");
+    }
+}
+
+const PACE_GRAPH: &str = r#"{
+    "animations": [{ "walk": { "start": 0, "count": 4, "animation_duration": 0.4, "playback": "loop" } }],
+    "variables": { "pace": 1.0, "steps": 1 },
+    "root": "walk",
+    "states": [{ "current": "walk", "next": "loop" }]
+}"#;
+
+fn frames_in_a_second(graph: &mut CharacterAnimationGraph) -> usize {
+    AnimationScript::new()
+        .ticks(100, 0.01)
+        .run(graph)
+        .unwrap()
+        .len()
+}
+
+#[test]
+fn speed_scales_how_fast_frames_change() {
+    let mut graph = graph(PACE_GRAPH);
+    frames_in_a_second(&mut graph);
+    assert_eq!(frames_in_a_second(&mut graph), 10);
+
+    graph.set_speed(2.0);
+    assert_eq!(frames_in_a_second(&mut graph), 20);
+
+    graph.set_speed(f32::INFINITY);
+    graph.set_speed(f32::NAN);
+    assert_eq!(graph.speed(), 2.0);
+
+    graph.set_speed(-1.0);
+    assert_eq!(frames_in_a_second(&mut graph), 0);
+
+    graph.set_speed(1e9);
+    assert_eq!(graph.speed(), MAX_SPEED);
+    assert!(frames_in_a_second(&mut graph) > 0);
+}
+
+#[test]
+fn paused_graph_shows_no_frames() {
+    let mut graph = graph(PACE_GRAPH);
+    frames_in_a_second(&mut graph);
+
+    graph.pause();
+    assert!(graph.is_paused());
+    assert_eq!(frames_in_a_second(&mut graph), 0);
+
+    graph.resume();
+    assert_eq!(frames_in_a_second(&mut graph), 10);
+}
+
+#[test]
+fn speed_follows_a_bound_variable() {
+    let mut graph = graph(PACE_GRAPH);
+    frames_in_a_second(&mut graph);
+    graph.bind_speed("pace").unwrap();
+
+    graph.set_variable("pace", Variable::Float(0.5));
+    assert_eq!(frames_in_a_second(&mut graph), 5);
+
+    graph.set_variable("pace", Variable::Float(f32::NAN));
+    graph.set_variable("pace", Variable::Float(f32::INFINITY));
+    assert_eq!(graph.variable("pace"), Some(&Variable::Float(0.5)));
+    assert_eq!(frames_in_a_second(&mut graph), 5);
+
+    graph.bind_speed("steps").unwrap();
+    graph.set_variable("steps", Variable::Int(-3));
+    frames_in_a_second(&mut graph);
+    assert_eq!(frames_in_a_second(&mut graph), 0);
+    graph.set_variable("steps", Variable::Int(i64::MAX));
+    assert!(frames_in_a_second(&mut graph) > 0);
+
+    graph.unbind_speed();
+    frames_in_a_second(&mut graph);
+    assert_eq!(frames_in_a_second(&mut graph), 10);
+    assert!(graph.bind_speed("missing").is_err());
+}
+
+#[test]
+fn layer_keeps_a_trigger_the_parent_already_used() {
+    let mut parent = graph(
+        r#"{
+            "animations": [
+                { "idle": { "start": 0, "count": 1, "animation_duration": 0.1, "playback": "loop" } },
+                { "attack": { "start": 1, "count": 2, "animation_duration": 0.2 } }
+            ],
+            "variables": { "attacking": "trigger" },
+            "root": "decide",
+            "conditions": [{
+                "name": "decide", "variables": ["attacking"], "values": [true],
+                "success": "attack", "failure": "idle"
+            }],
+            "states": [
+                { "current": "idle", "next": "loop" },
+                { "current": "attack", "next": "loop" }
+            ]
+        }"#,
+    );
+    let mut layer = graph(
+        r#"{
+            "animations": [
+                { "emote": { "start": 10, "count": 4, "animation_duration": 0.4 } },
+                { "rodAttack": { "start": 20, "count": 2, "animation_duration": 0.2 } }
+            ],
+            "variables": { "attacking": "trigger" },
+            "root": "decide",
+            "conditions": [{
+                "name": "decide", "variables": ["attacking"], "values": [true],
+                "success": "rodAttack", "failure": "emote"
+            }],
+            "states": [
+                { "current": "emote", "next": "loop", "locking": true },
+                { "current": "rodAttack", "next": "loop" }
+            ]
+        }"#,
+    );
+    let mut sync = AnimationLayer::default();
+    let mut tick = |parent: &mut CharacterAnimationGraph, layer: &mut CharacterAnimationGraph| {
+        sync.sync(parent, layer);
+        AnimationScript::new().tick(DELTA).run(parent).unwrap();
+        AnimationScript::new().tick(DELTA).run(layer).unwrap();
+    };
+
+    for _ in 0..6 {
+        tick(&mut parent, &mut layer);
+    }
+    assert_eq!(layer.current_state(), Some("emote"));
+
+    parent.set_variable("attacking", Variable::Trigger(true));
+    tick(&mut parent, &mut layer);
+    assert_eq!(parent.current_state(), Some("attack"));
+    assert_eq!(
+        parent.variable("attacking"),
+        Some(&Variable::Trigger(false))
+    );
+    assert_eq!(layer.current_state(), Some("emote"));
+
+    let mut states = Vec::new();
+    for _ in 0..10 {
+        tick(&mut parent, &mut layer);
+        states.extend(layer.current_state().map(str::to_string));
+    }
+    assert!(states.iter().any(|x| x == "rodAttack"));
+    assert_eq!(layer.variable("attacking"), Some(&Variable::Trigger(false)));
+}
+
+#[test]
+fn layer_gets_a_trigger_fired_twice_in_a_row() {
+    let json = r#"{
+        "animations": [
+            { "idle": { "start": 0, "count": 1, "animation_duration": 0.1, "playback": "loop" } },
+            { "attack": { "start": 1, "count": 2, "animation_duration": 0.2 } }
+        ],
+        "variables": { "attacking": "trigger" },
+        "root": "decide",
+        "conditions": [{
+            "name": "decide", "variables": ["attacking"], "values": [true],
+            "success": "attack", "failure": "idle"
+        }],
+        "states": [
+            { "current": "idle", "next": "loop" },
+            { "current": "attack", "next": "loop" }
+        ]
+    }"#;
+    let (mut parent, mut layer) = (graph(json), graph(json));
+    let mut sync = AnimationLayer::default();
+    let tick = |graph: &mut CharacterAnimationGraph| {
+        AnimationScript::new().tick(DELTA).run(graph).unwrap();
+    };
+
+    for _ in 0..2 {
+        parent.set_variable("attacking", Variable::Trigger(true));
+        sync.sync(&parent, &mut layer);
+        assert_eq!(layer.variable("attacking"), Some(&Variable::Trigger(true)));
+
+        tick(&mut parent);
+        tick(&mut layer);
+        assert_eq!(
+            parent.variable("attacking"),
+            Some(&Variable::Trigger(false))
+        );
+        assert_eq!(layer.variable("attacking"), Some(&Variable::Trigger(false)));
+        assert_eq!(layer.current_state(), Some("attack"));
+    }
+}
+
+#[test]
+fn layer_gets_variables_restored_on_the_parent() {
+    let mut parent = player_graph();
+    let mut layer = player_graph();
+    let mut sync = AnimationLayer::default();
+    sync.sync(&parent, &mut layer);
+
+    let mut walking = player_graph();
+    walking.set_variable("walking", Variable::Bool(true));
+    parent.restore(&walking.snapshot()).unwrap();
+    sync.sync(&parent, &mut layer);
+    assert_eq!(layer.variable("walking"), Some(&Variable::Bool(true)));
+}
+
+const NESTED_GRAPH: &str = r#"{
+    "animations": [
+        { "idle": { "start": 0, "count": 1, "animation_duration": 0.1 } },
+        { "step": { "start": 1, "count": 2, "animation_duration": 0.2 } },
+        { "wave": { "start": 3, "count": 1, "animation_duration": 0.1 } }
+    ],
+    "variables": { "walking": false },
+    "root": "decide",
+    "conditions": [{
+        "name": "decide", "variables": ["walking"], "values": [true],
+        "success": "locomotion", "failure": "idle"
+    }],
+    "states": [
+        { "current": "idle", "next": "loop" },
+        { "current": "wave", "next": "loop" }
+    ],
+    "subgraphs": [{ "name": "locomotion", "graph": "walking", "next": "wave" }],
+    "graphs": {
+        "walking": {
+            "root": "step",
+            "states": [{ "current": "step", "next": "greeting" }],
+            "subgraphs": [{ "name": "greeting", "graph": "greet", "next": "exit" }]
+        },
+        "greet": {
+            "root": "wave",
+            "states": [{ "current": "wave", "next": "exit" }]
+        }
+    }
+}"#;
+
+#[test]
+fn sub_graphs_are_entered_and_exited() {
+    let mut graph = graph(NESTED_GRAPH);
+    graph.log_transitions(64);
+    let samples = AnimationScript::new()
+        .set("walking", Variable::Bool(true))
+        .ticks(5, 0.1)
+        .run(&mut graph)
+        .unwrap();
+
+    let states = samples
+        .iter()
+        .map(|x| (x.node.as_ref(), x.frame))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        states[..5],
+        [
+            ("locomotion/step", 1),
+            ("locomotion/step", 2),
+            ("locomotion/greeting/wave", 3),
+            ("wave", 3),
+            ("locomotion/step", 1),
+        ]
+    );
+    assert_eq!(
+        transitions_of(&graph)[3..8],
+        [
+            "locomotion -> locomotion/step (entered sub-graph)",
+            "locomotion/step -> locomotion/greeting (animation finished)",
+            "locomotion/greeting -> locomotion/greeting/wave (entered sub-graph)",
+            "locomotion/greeting/wave -> wave (animation finished)",
+            "wave -> loop (animation finished)",
+        ]
+    );
+}
+
+#[test]
+fn every_named_graph_is_validated() {
+    let error = load_error(&NESTED_GRAPH.replace(
+        r#""graphs": {"#,
+        r#""graphs": {
+            "unused": { "root": "missing", "states": [{ "current": "lost", "next": "exit" }] },"#,
+    ));
+    assert!(error.contains("in graph `unused`: unknown node `missing` referenced by `root`"));
+
+    let error = load_error(&NESTED_GRAPH.replace(
+        r#""graphs": {"#,
+        r#""graphs": {
+            "unused": { "root": "lost", "states": [{ "current": "lost", "next": "exit" }] },"#,
+    ));
+    assert!(error.contains("in graph `unused`: invalid animation graph:"));
+    assert!(error.contains("`lost` plays `lost` which is not an animation"));
+}
+
+#[test]
+fn graphs_that_embed_themselves_are_rejected() {
+    let error = load_error(&NESTED_GRAPH.replace(
+        r#""states": [{ "current": "wave", "next": "exit" }]"#,
+        r#""states": [{ "current": "wave", "next": "again" }],
+            "subgraphs": [{ "name": "again", "graph": "walking", "next": "exit" }]"#,
+    ));
+    assert!(
+        error.contains("graph `walking` embeds itself through `locomotion/greeting/again`"),
+        "{error}"
+    );
+}
+
+#[test]
+fn snapshots_that_do_not_fit_the_graph_are_rejected() {
+    let mut played = graph(&looping_clip(6));
+    AnimationScript::new()
+        .ticks(9, 0.5)
+        .run(&mut played)
+        .unwrap();
+    let snapshot = played.snapshot();
+
+    let mut shortened = graph(&looping_clip(2));
+    let error = shortened.restore(&snapshot).unwrap_err().to_string();
+    assert!(error.contains("of `idle` in snapshot but it only has 2 frames"));
+    assert_eq!(shortened.current_state(), None);
+
+    let mut fast = snapshot.clone();
+    fast.speed = f32::INFINITY;
+    assert!(played.restore(&fast).is_err());
+
+    let mut overrun = snapshot.clone();
+    overrun.timer_elapsed = overrun.timer_duration * 2;
+    assert!(played.restore(&overrun).is_err());
+
+    assert!(played.restore(&snapshot).is_ok());
+}
+
+#[test]
+fn snapshots_bind_the_speed_only_to_numbers() {
+    let mut graph = graph(PACE_GRAPH);
+    graph.bind_speed("pace").unwrap();
+    let mut snapshot = graph.snapshot();
+    assert!(graph.restore(&snapshot).is_ok());
+
+    let mut player = player_graph();
+    snapshot = player.snapshot();
+    snapshot.speed_variable = Some("facing".to_string());
+    let error = player.restore(&snapshot).unwrap_err().to_string();
+    assert!(error.contains("speed can not be bound to `facing`"));
+}
+
+#[test]
+fn dot_output_labels_nodes_and_edges() {
+    let dot = definition(
+        r#"{
+            "animations": [
+                { "idle": { "start": 0, "count": 1, "animation_duration": 0.1, "playback": "loop" } },
+                { "attack": { "start": 1, "count": 2, "animation_duration": 0.2 } }
+            ],
+            "variables": { "attacking": "trigger", "mood": "calm, angry" },
+            "root": "decide",
+            "conditions": [{
+                "name": "decide", "variables": ["attacking"], "values": [true],
+                "success": "attack", "failure": "calm"
+            }],
+            "setters": [{ "name": "calm", "variables": ["mood"], "values": ["calm"], "next": "idle" }],
+            "states": [
+                { "current": "idle", "next": "loop" },
+                { "current": "attack", "next": "loop", "priority": 2 }
+            ]
+        }"#,
+    )
+    .to_dot();
+
+    assert!(dot.starts_with("digraph animation_graph {\n"));
+    assert!(dot.ends_with("}\n"));
+    for line in [
+        r#"    n0 [label="loop\nRoot", shape=doublecircle];"#,
+        r#"    n1 [label="decide\nCondition", shape=diamond];"#,
+        r#"    n2 [label="calm\nSetter", shape=parallelogram];"#,
+        r#"    n4 [label="attack\nState, priority 2", shape=box];"#,
+        r#"    n0 -> n1 [label=""];"#,
+        r#"    n1 -> n4 [label="attacking = true"];"#,
+        r#"    n1 -> n2 [label="otherwise"];"#,
+        r#"    n2 -> n3 [label="set mood = calm"];"#,
+        r#"    n4 -> n0 [label="finished"];"#,
+    ] {
+        assert!(dot.contains(line), "missing {line} in\n{dot}");
+    }
+}
+
+#[test]
+fn dot_output_escapes_quotes() {
+    let dot = definition(
+        r#"{
+            "animations": [{ "idle": { "start": 0, "count": 1, "animation_duration": 0.1, "playback": "loop" } }],
+            "variables": { "mood": "calm, \"angry\"" },
+            "root": "decide",
+            "switches": [{
+                "name": "decide", "variables": ["mood"],
+                "cases": [["\"angry\""]], "results": ["idle"], "default": "idle"
+            }],
+            "states": [{ "current": "idle", "next": "loop" }]
+        }"#,
+    )
+    .to_dot();
+
+    assert!(
+        dot.contains(r#"    n1 -> n2 [label="mood = \"angry\""];"#),
+        "{dot}"
+    );
+    assert!(dot.contains(r#"    n1 -> n2 [label="default"];"#), "{dot}");
+}
+
+#[test]
+fn script_samples_flip_like_the_sprite() {
+    let samples = AnimationScript::new()
+        .ticks(12, DELTA)
+        .run(&mut graph(TURN_GRAPH))
+        .unwrap();
+    let frames = samples
+        .iter()
+        .map(|x| (x.node.as_ref(), x.frame, x.flip_x))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        frames[..3],
+        [
+            ("turn", 0, false),
+            ("turn", 1, false),
+            ("walkRight", 4, true)
+        ]
+    );
+}
+
+#[test]
+fn snapshots_without_a_state_node_can_be_restored() {
+    let mut graph = player_graph();
+    walk_right().ticks(5, DELTA).run(&mut graph).unwrap();
+
+    let mut json = serde_json::to_value(graph.snapshot()).unwrap();
+    json.as_object_mut().unwrap().remove("state_node");
+    let snapshot: AnimationGraphSnapshot = serde_json::from_value(json).unwrap();
+
+    let mut restored = player_graph();
+    restored.restore(&snapshot).unwrap();
+    assert_eq!(restored.current_state(), Some("walk"));
+}
+
+#[test]
+fn aseprite_directions_keep_their_order_and_looping() {
+    let cases = [
+        ("forward", None, Playback::Loop, [0, 1, 2, 0, 1]),
+        ("forward", Some("2"), Playback::Once, [0, 1, 2, 10, 10]),
+        ("reverse", None, Playback::ReverseLoop, [2, 1, 0, 2, 1]),
+        ("reverse", Some("1"), Playback::Reverse, [2, 1, 0, 10, 10]),
+        ("pingpong", None, Playback::PingPong, [0, 1, 2, 1, 0]),
+        (
+            "pingpong_reverse",
+            None,
+            Playback::PingPongReverse,
+            [2, 1, 0, 1, 2],
+        ),
+    ];
+    for (direction, repeat, playback, expected) in cases {
+        let repeat = repeat
+            .map(|x| format!(r#", "repeat": "{x}""#))
+            .unwrap_or_default();
+        let sheet = AsepriteSheet::from_slice(
+            format!(
+                r#"{{
+                    "frames": [
+                        {{ "frame": {{ "x": 0, "y": 0, "w": 8, "h": 8 }}, "duration": 100 }},
+                        {{ "frame": {{ "x": 8, "y": 0, "w": 8, "h": 8 }}, "duration": 100 }},
+                        {{ "frame": {{ "x": 16, "y": 0, "w": 8, "h": 8 }}, "duration": 100 }}
+                    ],
+                    "meta": {{
+                        "size": {{ "w": 24, "h": 8 }},
+                        "frameTags": [{{ "name": "clip", "from": 0, "to": 2, "direction": "{direction}"{repeat} }}]
+                    }}
+                }}"#
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(sheet.animations["clip"].playback, playback, "{direction}");
+
+        let mut reader = GraphReader::from_json(
+            r#"{
+                "animations": [{ "after": { "start": 10, "count": 1, "animation_duration": 0.1, "playback": "loop" } }],
+                "root": "clip",
+                "states": [
+                    { "current": "clip", "next": "after" },
+                    { "current": "after", "next": "loop" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        reader.import_sheet(&sheet);
+        let samples = AnimationScript::new()
+            .ticks(16, DELTA)
+            .run(&mut reader.into_graph().unwrap())
+            .unwrap();
+        let frames = samples.iter().map(|x| x.frame).collect::<Vec<_>>();
+        assert_eq!(frames[..5], expected, "{direction}");
+    }
+}
+
+// How long each frame was shown before the next one replaced it.
+fn shown_for(samples: &[AnimationSample]) -> Vec<(usize, f32)> {
+    samples
+        .windows(2)
+        .map(|pair| (pair[0].frame, pair[1].time - pair[0].time))
+        .collect()
+}
+
+fn assert_shown_for(samples: &[AnimationSample], expected: &[(usize, f32)]) {
+    let shown = shown_for(samples);
+    assert!(shown.len() >= expected.len(), "{shown:?}");
+    for ((frame, time), (expected_frame, expected_time)) in shown.iter().zip(expected) {
+        assert_eq!(frame, expected_frame, "{shown:?}");
+        assert!((time - expected_time).abs() < 0.002, "{shown:?}");
+    }
+}
+
+#[test]
+fn animation_duration_scales_the_frame_durations() {
+    let mut graph = graph(
+        r#"{
+            "animations": [{
+                "idle": {
+                    "start": 0, "count": 2, "playback": "loop",
+                    "animation_duration": 0.2, "frame_durations": [0.1, 0.3]
+                }
+            }],
+            "root": "idle",
+            "states": [{ "current": "idle", "next": "loop" }]
+        }"#,
+    );
+    let samples = AnimationScript::new()
+        .ticks(1000, 0.001)
+        .run(&mut graph)
+        .unwrap();
+    assert_shown_for(&samples, &[(0, 0.05), (1, 0.15), (0, 0.05), (1, 0.15)]);
+}
+
+#[test]
+fn tags_play_over_a_new_animation_duration() {
+    let mut reader = GraphReader::from_json(
+        r#"{
+            "animations": [{
+                "attackRight": { "tag": "attackLeft", "animation_duration": 0.4, "playback": "loop" }
+            }],
+            "root": "attackRight",
+            "states": [{ "current": "attackRight", "next": "loop" }]
+        }"#,
+    )
+    .unwrap();
+    reader.import_sheet(&AsepriteSheet::from_slice(SHEET.as_bytes()).unwrap());
+
+    let samples = AnimationScript::new()
+        .ticks(1000, 0.001)
+        .run(&mut reader.into_graph().unwrap())
+        .unwrap();
+    assert_shown_for(&samples, &[(2, 0.1), (3, 0.3), (2, 0.1)]);
+}
+
+#[test]
+fn aseprite_hash_export_keeps_the_frame_order() {
+    let sheet = AsepriteSheet::from_slice(
+        br#"{
+            "frames": {
+                "player 1.aseprite": { "frame": { "x": 32, "y": 0, "w": 32, "h": 32 }, "duration": 100 },
+                "player 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 32, "h": 32 }, "duration": 200 }
+            },
+            "meta": {
+                "size": { "w": 64, "h": 32 },
+                "frameTags": [{ "name": "idle", "from": 0, "to": 1, "direction": "forward" }]
+            }
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(sheet.layout.textures[0].min, UVec2::new(32, 0));
+    assert_eq!(sheet.layout.textures[1].min, UVec2::new(0, 0));
+    assert_eq!(
+        sheet.animations["idle"].frame_durations,
+        Some(vec![0.1, 0.2])
+    );
+}
+
+// Ticks `graph` until it shows `frame`.
+fn tick_until_frame(graph: &mut CharacterAnimationGraph, frame: usize) {
+    for _ in 0..1000 {
+        if graph.get_next_index(0.001).unwrap().map(|x| x.index) == Some(frame) {
+            return;
+        }
+    }
+    panic!("frame {frame} was never shown");
+}
+
+#[test]
+fn last_attack_frame_still_locks() {
+    let mut graph = player_graph();
+    walk_right()
+        .tick(DELTA)
+        .set("attacking", Variable::Trigger(true))
+        .run(&mut graph)
+        .unwrap();
+
+    tick_until_frame(&mut graph, 45);
+    assert_eq!(graph.current_state(), Some("attack"));
+    assert!(graph.is_locking());
+    assert_eq!(graph.priority(), 1);
+    assert_eq!(graph.frames_remaining(), Some(0));
+}
+
+#[test]
+fn change_waits_for_the_last_attack_frame_to_finish() {
+    let mut graph = player_graph();
+    walk_right()
+        .tick(DELTA)
+        .set("attacking", Variable::Trigger(true))
+        .run(&mut graph)
+        .unwrap();
+    tick_until_frame(&mut graph, 45);
+
+    let samples = AnimationScript::new()
+        .tick(0.001)
+        .set("walking", Variable::Bool(false))
+        .ticks(400, 0.001)
+        .run(&mut graph)
+        .unwrap();
+
+    // Frame 45 was shown 1 ms before the script started.
+    assert_eq!(samples[0].node.as_ref(), "idle");
+    assert!((samples[0].time - 0.099).abs() < 0.002, "{samples:?}");
+    assert_eq!(samples[1].node.as_ref(), "idle");
+    assert!(
+        (samples[1].time - samples[0].time - 1.0 / 6.0).abs() < 0.002,
+        "{samples:?}"
+    );
+    assert_eq!(graph.pending_priority(), None);
+}
+
+#[test]
+fn attack_transitions_are_logged() {
+    let mut graph = player_graph();
+    graph.log_transitions(64);
+    walk_right()
+        .set("attacking", Variable::Trigger(true))
+        .ticks(8, 0.1)
+        .run(&mut graph)
+        .unwrap();
+
+    assert_eq!(
+        transitions_of(&graph)[..7],
+        [
+            "loop -> loop (changed walking, facing, attacking)",
+            "loop -> decideOnAttack (root)",
+            "decideOnAttack -> attack (condition succeeded)",
+            "attack -> loop (animation finished)",
+            "loop -> decideOnAttack (root)",
+            "decideOnAttack -> decideOnWalk (condition failed)",
+            "decideOnWalk -> walk (condition succeeded)",
+        ]
+    );
+}
+
+#[test]
+fn transition_log_keeps_only_the_latest_transitions() {
+    let script = walk_right()
+        .set("attacking", Variable::Trigger(true))
+        .ticks(8, 0.1);
+    let mut unlogged = player_graph();
+    script.run(&mut unlogged).unwrap();
+    assert_eq!(unlogged.transitions().count(), 0);
+
+    let mut full = player_graph();
+    full.log_transitions(64);
+    script.run(&mut full).unwrap();
+    let mut short = player_graph();
+    short.log_transitions(3);
+    script.run(&mut short).unwrap();
+
+    let full = transitions_of(&full);
+    assert!(full.len() > 3);
+    assert_eq!(transitions_of(&short), full[full.len() - 3..]);
+
+    short.log_transitions(2);
+    assert_eq!(transitions_of(&short), full[full.len() - 2..]);
+    short.log_transitions(0);
+    assert_eq!(short.transitions().count(), 0);
+}